@@ -12,6 +12,9 @@ pub struct Combatant {
     pub class: Classes,
     pub abilities: Option<Abilities>,
     pub hp: Meter<i32>,
+    /// Temporary hit points, lost before regular hp.
+    #[serde(default)]
+    pub temp_hp: Meter<i32>,
     pub hd: u32,
     pub attacks: Meter<u32>,
     pub ac: i32,
@@ -19,8 +22,21 @@ pub struct Combatant {
     pub status: Status,
     pub team: u32,
     pub init: u32,
+    /// Observer-only combatants are shown but take no part in combat.
+    #[serde(default)]
+    pub spectator: bool,
+    /// Remaining spell slots, with index 0 holding 1st level slots.
+    #[serde(default)]
+    pub spell_slots: Option<Vec<Meter<u32>>>,
+    /// Free-form reminders for the DM.
+    #[serde(default)]
+    pub notes: String,
     dealt: i32,
     recvd: i32,
+    #[serde(default)]
+    per_round_dealt: i32,
+    #[serde(default)]
+    per_round_recvd: i32,
     round: u32,
     xp_bonus: bool,
 }
@@ -30,12 +46,15 @@ pub struct Combatant {
 pub struct CombatantBuilder {
     pub name: String,
     pub class: Option<Classes>,
+    pub abilities: Option<Abilities>,
     pub hd: Option<u32>,
     pub hp: Option<Meter<i32>>,
     pub attacks: Option<Meter<u32>>,
     pub ac: Option<i32>,
     pub team: Option<u32>,
     pub init: Option<u32>,
+    #[serde(default)]
+    pub notes: String,
 }
 
 macro_rules! build_method {
@@ -52,16 +71,19 @@ impl CombatantBuilder {
         CombatantBuilder {
             name: n.into(),
             class: None,
+            abilities: None,
             hd: None,
             hp: None,
             attacks: None,
             ac: None,
             team: None,
             init: None,
+            notes: String::new(),
         }
     }
 
     build_method!(class, Classes);
+    build_method!(abilities, Abilities);
     build_method!(hd, u32);
     build_method!(hp, Meter<i32>);
     build_method!(attacks, Meter<u32>);
@@ -69,34 +91,110 @@ impl CombatantBuilder {
     build_method!(team, u32);
     build_method!(init, u32);
 
-    pub fn build(self) -> Option<Combatant> {
-        let class = self.class?;
-        Some(Combatant {
+    pub fn notes<S: Into<String>>(mut self, notes: S) -> Self {
+        self.notes = notes.into();
+        self
+    }
+
+    /// Build the combatant if all required fields are set.
+    /// Characters with abilities gain their constitution bonus for each hit die.
+    pub fn build(self) -> Result<Combatant, BuildError> {
+        let class = self.class.ok_or(BuildError::MissingClass)?;
+        let hd = self.hd.ok_or(BuildError::MissingHD)?;
+        let hp = self.hp.ok_or(BuildError::MissingHP)?;
+        let team = self.team.ok_or(BuildError::MissingTeam)?;
+        let init = self.init.ok_or(BuildError::MissingInit)?;
+        // a low constitution never takes a hit die below 1 hp,
+        // and an unset hp is left for the check below
+        let hp = match (&class, self.abilities) {
+            (&Classes::Monster { .. }, _) | (_, None) => hp,
+            (_, Some(a)) if hp.max() != 0 => {
+                let floor = (hd as i32 - hp.max()).min(0);
+                hp.raise((a.con_hp_bonus() * hd as i32).max(floor))
+            },
+            _ => hp,
+        };
+        // 0 is used to mark these as unset
+        if hp.max() == 0 {
+            return Err(BuildError::ZeroHP);
+        }
+        if team == 0 {
+            return Err(BuildError::ZeroTeam);
+        }
+        if init == 0 {
+            return Err(BuildError::ZeroInit);
+        }
+        Ok(Combatant {
             name: self.name,
-            class: class,
-            hd: self.hd?,
-            hp: self.hp?,
-            attacks: self.attacks?,
-            ac: self.ac?,
-            team: self.team?,
-            init: self.init?,
+            class: class.clone(),
+            hd,
+            hp,
+            temp_hp: Meter::default(),
+            attacks: self.attacks.ok_or(BuildError::MissingAttacks)?,
+            ac: self.ac.ok_or(BuildError::MissingAC)?,
+            team,
+            init,
             status: Status::Healthy,
-            abilities: None,
+            abilities: self.abilities,
+            spell_slots: class.default_spell_slots(class.level())
+                .map(|v| v.into_iter().map(|n| Meter::new(n, n)).collect()),
             thac0: class.thac0(),
+            spectator: false,
+            notes: self.notes,
             dealt: 0,
             recvd: 0,
+            per_round_dealt: 0,
+            per_round_recvd: 0,
             round: 1,
-            xp_bonus: false,
+            xp_bonus: match (&class, self.abilities) {
+                (&Classes::Single { name: c, .. }, Some(a)) => a.qualifies_for_xp_bonus(c),
+                (Classes::Multi { name: v, .. }, Some(a)) => {
+                    v.iter().all(|&c| a.qualifies_for_xp_bonus(c))
+                },
+                _ => false,
+            },
         })
     }
 }
 
+/// The field missing when a combatant cannot be built.
+#[derive(Debug, PartialEq, Fail)]
+pub enum BuildError {
+    #[fail(display = "Cannot build: missing class")]
+    MissingClass,
+    #[fail(display = "Cannot build: missing HD")]
+    MissingHD,
+    #[fail(display = "Cannot build: missing HP")]
+    MissingHP,
+    #[fail(display = "Cannot build: missing attacks")]
+    MissingAttacks,
+    #[fail(display = "Cannot build: missing AC")]
+    MissingAC,
+    #[fail(display = "Cannot build: missing team")]
+    MissingTeam,
+    #[fail(display = "Cannot build: missing initiative")]
+    MissingInit,
+    #[fail(display = "Cannot build: max HP must not be 0")]
+    ZeroHP,
+    #[fail(display = "Cannot build: team must not be 0")]
+    ZeroTeam,
+    #[fail(display = "Cannot build: initiative must not be 0")]
+    ZeroInit,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Classes {
     Multi { name: Vec<Class>, lvl: u32 },
     Single { name: Class, lvl: u32 },
-    Monster { magical: bool, hd: u32 },
+    /// Monsters may have fractional hit dice, e.g. 1/2 for kobolds.
+    Monster {
+        magical: bool,
+        #[serde(rename = "hd")]
+        hd_num: u32,
+        #[serde(default = "Classes::whole_den")]
+        hd_den: u32,
+    },
 }
 
 impl Classes {
@@ -109,55 +207,212 @@ impl Classes {
     // Thieves, assassins and bards
     const THIEF_THAC0 : [u32; 13] = [21, 21, 20, 20, 19, 19, 18, 18, 17, 17, 16, 16, 15];
 
+    // Spell slots per spell level (1st to 7th) by caster level
+    // Clerics
+    const CLERIC_SPELLS : [[u32; 7]; 13] = [
+        [1, 0, 0, 0, 0, 0, 0],
+        [2, 0, 0, 0, 0, 0, 0],
+        [2, 1, 0, 0, 0, 0, 0],
+        [3, 2, 0, 0, 0, 0, 0],
+        [3, 3, 1, 0, 0, 0, 0],
+        [3, 3, 2, 0, 0, 0, 0],
+        [3, 3, 2, 1, 0, 0, 0],
+        [3, 3, 3, 2, 0, 0, 0],
+        [4, 4, 3, 2, 1, 0, 0],
+        [4, 4, 3, 3, 2, 0, 0],
+        [5, 4, 4, 3, 2, 1, 0],
+        [6, 5, 5, 3, 2, 2, 0],
+        [6, 6, 6, 4, 2, 2, 0],
+    ];
+    // Druids
+    const DRUID_SPELLS : [[u32; 7]; 13] = [
+        [2, 0, 0, 0, 0, 0, 0],
+        [2, 1, 0, 0, 0, 0, 0],
+        [3, 2, 1, 0, 0, 0, 0],
+        [4, 2, 2, 0, 0, 0, 0],
+        [4, 3, 2, 0, 0, 0, 0],
+        [4, 3, 2, 1, 0, 0, 0],
+        [4, 4, 3, 1, 0, 0, 0],
+        [4, 4, 3, 2, 0, 0, 0],
+        [5, 4, 3, 2, 1, 0, 0],
+        [5, 4, 3, 3, 2, 0, 0],
+        [5, 5, 3, 3, 2, 1, 0],
+        [5, 5, 4, 4, 3, 2, 1],
+        [6, 5, 5, 5, 4, 3, 2],
+    ];
+    // Mages
+    const MAGE_SPELLS : [[u32; 7]; 13] = [
+        [1, 0, 0, 0, 0, 0, 0],
+        [2, 0, 0, 0, 0, 0, 0],
+        [2, 1, 0, 0, 0, 0, 0],
+        [3, 2, 0, 0, 0, 0, 0],
+        [4, 2, 1, 0, 0, 0, 0],
+        [4, 2, 2, 0, 0, 0, 0],
+        [4, 3, 2, 1, 0, 0, 0],
+        [4, 3, 3, 2, 0, 0, 0],
+        [4, 3, 3, 2, 1, 0, 0],
+        [4, 4, 3, 2, 2, 0, 0],
+        [4, 4, 4, 3, 3, 0, 0],
+        [4, 4, 4, 4, 4, 1, 0],
+        [5, 5, 5, 4, 4, 2, 0],
+    ];
+    // Illusionists
+    const ILLUSIONIST_SPELLS : [[u32; 7]; 13] = [
+        [1, 0, 0, 0, 0, 0, 0],
+        [2, 0, 0, 0, 0, 0, 0],
+        [2, 1, 0, 0, 0, 0, 0],
+        [3, 2, 0, 0, 0, 0, 0],
+        [4, 2, 1, 0, 0, 0, 0],
+        [4, 3, 1, 0, 0, 0, 0],
+        [4, 3, 2, 0, 0, 0, 0],
+        [4, 3, 2, 1, 0, 0, 0],
+        [5, 3, 3, 2, 0, 0, 0],
+        [5, 4, 3, 2, 1, 0, 0],
+        [5, 4, 4, 3, 1, 0, 0],
+        [5, 5, 4, 3, 2, 1, 0],
+        [5, 5, 4, 3, 2, 2, 0],
+    ];
+    // Bards
+    const BARD_SPELLS : [[u32; 7]; 13] = [
+        [0, 0, 0, 0, 0, 0, 0],
+        [1, 0, 0, 0, 0, 0, 0],
+        [2, 0, 0, 0, 0, 0, 0],
+        [2, 1, 0, 0, 0, 0, 0],
+        [3, 1, 0, 0, 0, 0, 0],
+        [3, 2, 0, 0, 0, 0, 0],
+        [3, 2, 1, 0, 0, 0, 0],
+        [3, 3, 1, 0, 0, 0, 0],
+        [3, 3, 2, 0, 0, 0, 0],
+        [3, 3, 2, 1, 0, 0, 0],
+        [3, 3, 3, 1, 0, 0, 0],
+        [3, 3, 3, 2, 0, 0, 0],
+        [3, 3, 3, 2, 1, 0, 0],
+    ];
+
+    // Base xp awarded for monsters by hd, from under 1 up to 13 or more
+    const MONSTER_XP : [i32; 14] = [5, 10, 20, 35, 60, 90, 150, 225, 375, 600, 900, 1100, 1300, 1500];
+
+    /// Return the denominator of whole hit dice.
+    fn whole_den() -> u32 {
+        1
+    }
+
+    /// Return the number of whole hit dice, so fractions under 1 HD give 0.
+    fn whole_hd(num: u32, den: u32) -> u32 {
+        num / den.max(1)
+    }
+
     /// Return a new Classes variant with the given hd/level.
     pub fn lvl(mut self, lvl: u32) -> Self {
         self = match self {
-            Classes::Multi { name: n, .. } => Classes::Multi { name: n.clone(), lvl: lvl },
-            Classes::Single { name: n, .. } => Classes::Single { name: n, lvl: lvl },
-            Classes::Monster { magical: m, .. } => Classes::Monster { magical: m, hd: lvl },
+            Classes::Multi { name: n, .. } => Classes::Multi { name: n.clone(), lvl },
+            Classes::Single { name: n, .. } => Classes::Single { name: n, lvl },
+            Classes::Monster { magical: m, .. } => {
+                Classes::Monster { magical: m, hd_num: lvl, hd_den: 1 }
+            },
         };
         self
     }
 
+    /// Return the spell slots per spell level for a caster of the given level,
+    /// or None for classes that cannot cast. Multi-classed casters pool their slots.
+    pub fn default_spell_slots(&self, lvl: u32) -> Option<Vec<u32>> {
+        let idx = if lvl <= 1 {
+            0usize
+        } else {
+            (lvl as usize - 1).min(Classes::MAGE_SPELLS.len() - 1)
+        };
+        let tables = match *self {
+            Classes::Multi { name: ref v, .. } => v.iter().filter_map(|c| c.spells()).collect(),
+            Classes::Single { name: c, .. } => c.spells().into_iter().collect(),
+            Classes::Monster { .. } => vec![],
+        };
+        if tables.is_empty() {
+            return None;
+        }
+        let mut slots = (0..7)
+            .map(|l| tables.iter().map(|t| t[idx][l]).sum())
+            .collect::<Vec<u32>>();
+        // drop spell levels that cannot be cast yet
+        while slots.last() == Some(&0) {
+            slots.pop();
+        }
+        Some(slots)
+    }
+
+    /// Return the base xp awarded for defeating a monster,
+    /// doubled for magical monsters. Characters award none.
+    pub fn base_xp(&self) -> i32 {
+        match *self {
+            Classes::Monster { magical: m, hd_num: n, hd_den: d } => {
+                let h = Classes::whole_hd(n, d) as usize;
+                let xp = Classes::MONSTER_XP[h.min(Classes::MONSTER_XP.len() - 1)];
+                if m { xp * 2 } else { xp }
+            },
+            _ => 0,
+        }
+    }
+
+    /// Raise the level by one, up to the end of the THAC0 tables, or the hd by one
+    /// for monsters. Monster hd are not capped since their THAC0 lookup holds at the end.
+    fn increment_lvl(&mut self) {
+        let max = Classes::FIGHTER_THAC0.len() as u32;
+        match *self {
+            Classes::Multi { ref mut lvl, .. } | Classes::Single { ref mut lvl, .. } => {
+                *lvl = (*lvl + 1).min(max);
+            },
+            // fractional hit dice rise to a single whole hit die
+            Classes::Monster { ref mut hd_num, ref mut hd_den, .. } => {
+                *hd_num = Classes::whole_hd(*hd_num, *hd_den) + 1;
+                *hd_den = 1;
+            },
+        }
+    }
+
+    /// Return the level, or whole hd for monsters.
+    pub fn level(&self) -> u32 {
+        match *self {
+            Classes::Multi { lvl: l, .. } | Classes::Single { lvl: l, .. } => l,
+            Classes::Monster { hd_num: n, hd_den: d, .. } => Classes::whole_hd(n, d),
+        }
+    }
+
+    /// Look up the given level in a progression table,
+    /// holding at the last entry for levels beyond the end of the table.
+    fn progression(table: &[u32], lvl: u32) -> u32 {
+        let idx = if lvl <= 1 {
+            0usize
+        } else {
+            (lvl as usize - 1).min(table.len() - 1)
+        };
+        table[idx]
+    }
+
     /// Return THAC0 associated with the given class and level.
+    /// Multi-classed characters use the best THAC0 of their classes,
+    /// each looked up in its own progression.
     pub fn thac0(&self) -> u32 {
         match *self {
             Classes::Multi { name: ref v, lvl: l } => {
-                let idx = if l <= 1 {
-                    0usize
-                } else {
-                    (l as usize - 1).min(Classes::FIGHTER_THAC0.len() - 1)
-                };
-                v.iter().map(|c| {
-                    match *c {
-                        Class::Cleric | Class::Druid | Class::Monk => Classes::CLERIC_THAC0[idx],
-                        Class::Fighter | Class::Paladin | Class::Ranger => Classes::FIGHTER_THAC0[idx],
-                        Class::Mage | Class::Illusionist => Classes::MAGE_THAC0[idx],
-                        Class::Thief | Class::Assassin | Class::Bard => Classes::THIEF_THAC0[idx],
-                    }
-                }).min().unwrap_or(20)
+                v.iter().map(|c| Classes::progression(c.thac0_table(), l)).min().unwrap_or(20)
             },
-            Classes::Single { name: c, lvl: l } => {
-                let idx = if l <= 1 {
-                    0usize
-                } else {
-                    (l as usize - 1).min(Classes::FIGHTER_THAC0.len() - 1)
-                };
-                match c {
-                    Class::Cleric | Class::Druid | Class::Monk => Classes::CLERIC_THAC0[idx],
-                    Class::Fighter | Class::Paladin | Class::Ranger => Classes::FIGHTER_THAC0[idx],
-                    Class::Mage | Class::Illusionist => Classes::MAGE_THAC0[idx],
-                    Class::Thief | Class::Assassin | Class::Bard => Classes::THIEF_THAC0[idx],
-                }
-            },
-            Classes::Monster { hd: h, .. } => {
-                let idx = if h <= 1 {
-                    0usize
-                } else {
-                    (h as usize - 1).min(Classes::FIGHTER_THAC0.len() - 1)
-                };
-                Classes::FIGHTER_THAC0[idx]
+            Classes::Single { name: c, lvl: l } => Classes::progression(c.thac0_table(), l),
+            Classes::Monster { .. } => Classes::progression(&Classes::FIGHTER_THAC0, self.level()),
+        }
+    }
+}
+
+impl Classes {
+    /// Return the saving throw target for the given category.
+    /// Multi-classed characters use the best target of their classes.
+    pub fn save_target(&self, cat: SaveCategory) -> u32 {
+        match *self {
+            Classes::Multi { name: ref v, lvl: l } => {
+                v.iter().map(|c| c.saves().target(cat, l)).min().unwrap_or(20)
             },
+            Classes::Single { name: c, lvl: l } => c.saves().target(cat, l),
+            Classes::Monster { magical: true, .. } => Saves::MAGICAL.target(cat, self.level()),
+            Classes::Monster { magical: false, .. } => Saves::MONSTER.target(cat, self.level()),
         }
     }
 }
@@ -168,6 +423,8 @@ pub enum ParseClassError {
     Int(#[cause] ParseIntError),
     #[fail(display = "Invalid class name")]
     Name,
+    #[fail(display = "Hit dice cannot have a denominator of 0")]
+    ZeroHD,
 }
 
 impl From<ParseIntError> for ParseClassError {
@@ -176,17 +433,45 @@ impl From<ParseIntError> for ParseClassError {
     }
 }
 
+impl Classes {
+    /// Return a short form for display in narrow columns, e.g. "F3/M3" or "4HD!".
+    pub fn abbrev(&self) -> String {
+        match *self {
+            Classes::Multi { name: ref v, lvl: l } => v.iter()
+                .map(|c| format!("{}{}", c.abbrev(), l))
+                .collect::<Vec<_>>()
+                .join("/"),
+            Classes::Single { name: c, lvl: l } => format!("{}{}", c.abbrev(), l),
+            Classes::Monster { magical: m, hd_num: n, hd_den: d } => {
+                format!("{}HD{}", Classes::hd_string(n, d), if m { "!" } else { "" })
+            },
+        }
+    }
+}
+
+impl Classes {
+    /// Format hit dice as a whole number or a fraction.
+    fn hd_string(num: u32, den: u32) -> String {
+        if den > 1 {
+            format!("{}/{}", num, den)
+        } else {
+            num.to_string()
+        }
+    }
+}
+
 impl fmt::Display for Classes {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            &Classes::Multi { name: ref v, lvl: l} => {
+        match *self {
+            Classes::Multi { name: ref v, lvl: l} => {
                 let names = v.iter().map(|&c| format!("{}", c))
                 .collect::<Vec<_>>().join("/");
                 write!(f, "{} level {}", l, names)
             },
-            &Classes::Single { name: c, lvl: l } => write!(f, "{} level {}", l, c),
-            &Classes::Monster { magical: m, hd: h } => {
-                write!(f, "{}{}-HD monster", if m { "magical " } else { "" }, h)
+            Classes::Single { name: c, lvl: l } => write!(f, "{} level {}", l, c),
+            Classes::Monster { magical: m, hd_num: n, hd_den: d } => {
+                write!(f, "{}{}-HD monster", if m { "magical " } else { "" },
+                       Classes::hd_string(n, d))
             },
         }
     }
@@ -196,17 +481,25 @@ impl FromStr for Classes {
     type Err = ParseClassError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         // detach optional numeric portion
-        let (s, n) = s.find(char::is_numeric).and_then(|i| {
-            // split, return num and shortened s
-            let (s, nums) = s.split_at(i);
-            nums.parse::<u32>().map(|n| (s, n)).ok()
-        }).unwrap_or((s, 1));
+        let (s, nums) = match s.find(char::is_numeric) {
+            Some(i) => s.split_at(i),
+            None => (s, ""),
+        };
         match s {
-            // magical monsters: ![N]
-            "!" => Ok(Classes::Monster { magical: true, hd: n }),
-            // regular monsters .[N]
-            "." => Ok(Classes::Monster { magical: false, hd: n }),
+            // magical monsters: ![N] or ![N/D], regular monsters: .[N] or .[N/D]
+            "!" | "." => {
+                let (hd_num, hd_den) = match nums.find('/') {
+                    Some(i) => (nums[..i].parse::<u32>()?, nums[i + 1..].parse::<u32>()?),
+                    None if nums.is_empty() => (1, 1),
+                    None => (nums.parse::<u32>()?, 1),
+                };
+                if hd_den == 0 {
+                    return Err(ParseClassError::ZeroHD);
+                }
+                Ok(Classes::Monster { magical: s == "!", hd_num, hd_den })
+            },
             _ => {
+                let n = if nums.is_empty() { 1 } else { nums.parse::<u32>()? };
                 let classes : Result<Vec<Class>, ParseClassError> = s.split("/")
                     .map(|c| c.parse::<Class>()).collect();
                 classes.and_then(|c| if c.len() > 1 {
@@ -254,6 +547,54 @@ impl fmt::Display for Class {
     }
 }
 
+impl Class {
+    /// Return the record for the class.
+    fn record(&self) -> &'static ClassRecord {
+        CLASS_RECORDS.iter()
+            .find(|r| r.name == *self)
+            .expect("every class has a record")
+    }
+
+    /// Return the short form of the class name.
+    pub fn abbrev(&self) -> &'static str {
+        match *self {
+            Class::Cleric => "C",
+            Class::Druid => "D",
+            Class::Fighter => "F",
+            Class::Paladin => "P",
+            Class::Ranger => "R",
+            Class::Mage => "M",
+            Class::Illusionist => "I",
+            Class::Thief => "T",
+            Class::Assassin => "A",
+            Class::Monk => "Mo",
+            Class::Bard => "B",
+        }
+    }
+
+    /// Return the THAC0 progression for the class.
+    fn thac0_table(&self) -> &'static [u32] {
+        &self.record().thac0
+    }
+
+    /// Return the spell slot table for the class, if it casts spells.
+    fn spells(&self) -> Option<&'static [[u32; 7]; 13]> {
+        match *self {
+            Class::Cleric => Some(&Classes::CLERIC_SPELLS),
+            Class::Druid => Some(&Classes::DRUID_SPELLS),
+            Class::Mage => Some(&Classes::MAGE_SPELLS),
+            Class::Illusionist => Some(&Classes::ILLUSIONIST_SPELLS),
+            Class::Bard => Some(&Classes::BARD_SPELLS),
+            _ => None,
+        }
+    }
+
+    /// Return the saving throw table for the class.
+    fn saves(&self) -> &'static Saves {
+        &self.record().saves
+    }
+}
+
 impl FromStr for Class {
     type Err = ParseClassError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -277,13 +618,112 @@ impl FromStr for Class {
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct ClassRecord {
     name: Class,
-    xp: Abilities,
-    multi: Abilities,
+    /// Minimum scores required to take the class.
+    #[allow(dead_code)]
     min: Abilities,
+    /// Minimum scores required to take the class as part of a multi-class.
+    #[allow(dead_code)]
+    multi: Abilities,
+    /// Minimum scores required to earn bonus xp.
+    xp: Abilities,
     thac0: [u32; 13],
     saves: Saves,
 }
 
+/// Ability requirements, THAC0 and saving throws for each class, as listed in classes.csv.
+/// Requirements of 0 are unrestricted, and 99 marks a class that never earns bonus xp.
+static CLASS_RECORDS : &[ClassRecord] = &[
+    ClassRecord {
+        name: Class::Cleric,
+        min: Abilities::scores(0, 0, 8, 0, 0, 0),
+        multi: Abilities::scores(0, 0, 13, 0, 0, 0),
+        xp: Abilities::scores(0, 0, 16, 0, 0, 0),
+        thac0: Classes::CLERIC_THAC0,
+        saves: Saves::CLERIC,
+    },
+    ClassRecord {
+        name: Class::Druid,
+        min: Abilities::scores(0, 0, 11, 0, 0, 14),
+        multi: Abilities::scores(0, 0, 13, 0, 0, 15),
+        xp: Abilities::scores(0, 0, 16, 0, 0, 16),
+        thac0: Classes::CLERIC_THAC0,
+        saves: Saves::DRUID,
+    },
+    ClassRecord {
+        name: Class::Fighter,
+        min: Abilities::scores(8, 0, 0, 0, 6, 0),
+        multi: Abilities::scores(13, 0, 0, 0, 7, 0),
+        xp: Abilities::scores(16, 0, 0, 0, 7, 0),
+        thac0: Classes::FIGHTER_THAC0,
+        saves: Saves::FIGHTER,
+    },
+    ClassRecord {
+        name: Class::Paladin,
+        min: Abilities::scores(11, 8, 12, 0, 8, 16),
+        multi: Abilities::scores(15, 9, 15, 0, 9, 17),
+        xp: Abilities::scores(16, 9, 16, 0, 9, 17),
+        thac0: Classes::FIGHTER_THAC0,
+        saves: Saves::PALADIN,
+    },
+    ClassRecord {
+        name: Class::Ranger,
+        min: Abilities::scores(12, 12, 13, 0, 13, 0),
+        multi: Abilities::scores(15, 15, 15, 0, 14, 0),
+        xp: Abilities::scores(16, 16, 16, 0, 14, 0),
+        thac0: Classes::FIGHTER_THAC0,
+        saves: Saves::RANGER,
+    },
+    ClassRecord {
+        name: Class::Mage,
+        min: Abilities::scores(0, 8, 0, 5, 0, 0),
+        multi: Abilities::scores(0, 16, 0, 6, 0, 0),
+        xp: Abilities::scores(0, 16, 0, 6, 0, 0),
+        thac0: Classes::MAGE_THAC0,
+        saves: Saves::MAGE,
+    },
+    ClassRecord {
+        name: Class::Illusionist,
+        min: Abilities::scores(0, 14, 0, 15, 0, 0),
+        multi: Abilities::scores(0, 16, 0, 16, 0, 0),
+        xp: Abilities::scores(99, 99, 99, 99, 99, 99),
+        thac0: Classes::MAGE_THAC0,
+        saves: Saves::ILLUSIONIST,
+    },
+    ClassRecord {
+        name: Class::Thief,
+        min: Abilities::scores(0, 0, 0, 8, 0, 0),
+        multi: Abilities::scores(0, 0, 0, 13, 0, 0),
+        xp: Abilities::scores(0, 0, 0, 16, 0, 0),
+        thac0: Classes::THIEF_THAC0,
+        saves: Saves::THIEF,
+    },
+    ClassRecord {
+        name: Class::Assassin,
+        min: Abilities::scores(11, 10, 0, 11, 0, 0),
+        multi: Abilities::scores(15, 11, 0, 15, 0, 0),
+        xp: Abilities::scores(99, 99, 99, 99, 99, 99),
+        thac0: Classes::THIEF_THAC0,
+        saves: Saves::ASSASSIN,
+    },
+    ClassRecord {
+        name: Class::Monk,
+        min: Abilities::scores(14, 0, 14, 14, 10, 0),
+        multi: Abilities::scores(16, 0, 16, 16, 16, 0),
+        xp: Abilities::scores(99, 99, 99, 99, 99, 99),
+        thac0: Classes::CLERIC_THAC0,
+        saves: Saves::MONK,
+    },
+    ClassRecord {
+        name: Class::Bard,
+        min: Abilities::scores(5, 9, 12, 5, 5, 14),
+        multi: Abilities::scores(6, 10, 15, 6, 6, 15),
+        xp: Abilities::scores(99, 99, 99, 99, 99, 99),
+        thac0: Classes::THIEF_THAC0,
+        saves: Saves::BARD,
+    },
+];
+
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Saves {
     poison: [u32; 20],
@@ -294,12 +734,187 @@ pub struct Saves {
     magic: [u32; 20],
 }
 
+impl Saves {
+    // Saving throw targets for levels 1 to 20, as listed in classes.csv.
+    const CLERIC : Saves = Saves {
+        poison: [12, 12, 12, 12, 11, 11, 11, 11, 9, 9, 9, 9, 9, 8, 8, 8, 8, 8, 7, 7],
+        para: [11, 11, 11, 11, 10, 10, 10, 10, 8, 8, 8, 8, 8, 7, 7, 7, 7, 7, 6, 6],
+        poly: [13, 13, 13, 13, 12, 12, 12, 12, 10, 10, 10, 10, 10, 9, 9, 9, 9, 9, 8, 8],
+        rsw: [14, 14, 14, 14, 13, 13, 13, 13, 11, 11, 11, 11, 11, 10, 10, 10, 10, 10, 9, 9],
+        breath: [15, 15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 12, 12, 11, 11, 11, 11, 11, 10, 10],
+        magic: [15, 15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 12, 12, 11, 11, 11, 11, 11, 10, 10],
+    };
+    const DRUID : Saves = Saves {
+        poison: [12, 12, 12, 12, 11, 11, 11, 11, 9, 9, 9, 9, 9, 8, 8, 8, 8, 8, 7, 7],
+        para: [11, 11, 11, 11, 10, 10, 10, 10, 8, 8, 8, 8, 8, 7, 7, 7, 7, 7, 6, 6],
+        poly: [13, 13, 13, 13, 12, 12, 12, 12, 10, 10, 10, 10, 10, 9, 9, 9, 9, 9, 8, 8],
+        rsw: [14, 14, 14, 14, 13, 13, 13, 13, 11, 11, 11, 11, 11, 10, 10, 10, 10, 10, 9, 9],
+        breath: [15, 15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 12, 12, 11, 11, 11, 11, 11, 10, 10],
+        magic: [15, 15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 12, 12, 11, 11, 11, 11, 11, 10, 10],
+    };
+    const FIGHTER : Saves = Saves {
+        poison: [15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 11, 11, 11, 11, 11, 9, 9, 9, 9, 9],
+        para: [15, 15, 15, 14, 14, 14, 14, 13, 13, 13, 12, 12, 12, 12, 12, 10, 10, 10, 10, 10],
+        poly: [16, 16, 16, 15, 15, 15, 15, 13, 13, 13, 11, 11, 11, 11, 11, 10, 10, 10, 10, 10],
+        rsw: [15, 15, 15, 13, 13, 13, 13, 12, 12, 12, 10, 10, 10, 10, 10, 9, 9, 9, 9, 9],
+        breath: [17, 17, 17, 16, 16, 16, 16, 14, 14, 14, 13, 13, 13, 13, 13, 11, 11, 11, 11, 11],
+        magic: [15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 11, 11, 11, 11, 11, 9, 9, 9, 9, 9],
+    };
+    const PALADIN : Saves = Saves {
+        poison: [12, 12, 12, 12, 11, 11, 11, 11, 9, 9, 9, 9, 9, 8, 8, 8, 8, 8, 7, 7],
+        para: [11, 11, 11, 11, 10, 10, 10, 10, 8, 8, 8, 8, 8, 7, 7, 7, 7, 7, 6, 6],
+        poly: [13, 13, 13, 13, 12, 12, 12, 12, 10, 10, 10, 10, 10, 9, 9, 9, 9, 9, 8, 8],
+        rsw: [14, 14, 14, 14, 13, 13, 13, 13, 11, 11, 11, 11, 11, 10, 10, 10, 10, 10, 9, 9],
+        breath: [15, 15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 12, 12, 11, 11, 11, 11, 11, 10, 10],
+        magic: [15, 15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 12, 12, 11, 11, 11, 11, 11, 10, 10],
+    };
+    const RANGER : Saves = Saves {
+        poison: [15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 11, 11, 11, 11, 11, 9, 9, 9, 9, 9],
+        para: [15, 15, 15, 14, 14, 14, 14, 13, 13, 13, 12, 12, 12, 12, 12, 10, 10, 10, 10, 10],
+        poly: [16, 16, 16, 15, 15, 15, 15, 13, 13, 13, 11, 11, 11, 11, 11, 10, 10, 10, 10, 10],
+        rsw: [15, 15, 15, 13, 13, 13, 13, 12, 12, 12, 10, 10, 10, 10, 10, 9, 9, 9, 9, 9],
+        breath: [17, 17, 17, 16, 16, 16, 16, 14, 14, 14, 13, 13, 13, 13, 13, 11, 11, 11, 11, 11],
+        magic: [15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 11, 11, 11, 11, 11, 9, 9, 9, 9, 9],
+    };
+    const MAGE : Saves = Saves {
+        poison: [14, 14, 14, 14, 13, 13, 13, 13, 11, 11, 11, 11, 10, 10, 10, 10, 10, 8, 8, 8],
+        para: [12, 12, 12, 12, 11, 11, 11, 11, 10, 10, 10, 10, 9, 9, 9, 9, 9, 8, 8, 8],
+        poly: [12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6, 6, 6, 6, 6, 4, 4, 4],
+        rsw: [11, 11, 11, 11, 9, 9, 9, 9, 7, 7, 7, 7, 5, 5, 5, 5, 5, 3, 3, 3],
+        breath: [15, 15, 15, 15, 13, 13, 13, 13, 11, 11, 11, 11, 9, 9, 9, 9, 9, 7, 7, 7],
+        magic: [12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6, 6, 6, 6, 6, 4, 4, 4],
+    };
+    const ILLUSIONIST : Saves = Saves {
+        poison: [14, 14, 14, 14, 13, 13, 13, 13, 11, 11, 11, 11, 10, 10, 10, 10, 10, 8, 8, 8],
+        para: [12, 12, 12, 12, 11, 11, 11, 11, 10, 10, 10, 10, 9, 9, 9, 9, 9, 8, 8, 8],
+        poly: [12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6, 6, 6, 6, 6, 4, 4, 4],
+        rsw: [11, 11, 11, 11, 9, 9, 9, 9, 7, 7, 7, 7, 5, 5, 5, 5, 5, 3, 3, 3],
+        breath: [15, 15, 15, 15, 13, 13, 13, 13, 11, 11, 11, 11, 9, 9, 9, 9, 9, 7, 7, 7],
+        magic: [12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6, 6, 6, 6, 6, 4, 4, 4],
+    };
+    const THIEF : Saves = Saves {
+        poison: [13, 13, 13, 13, 13, 13, 12, 12, 12, 12, 12, 11, 11, 11, 11, 10, 10, 10, 10, 9],
+        para: [12, 12, 12, 12, 12, 12, 11, 11, 11, 11, 11, 10, 10, 10, 10, 9, 9, 9, 9, 8],
+        poly: [14, 14, 14, 14, 14, 14, 13, 13, 13, 13, 13, 12, 12, 12, 12, 11, 11, 11, 11, 10],
+        rsw: [14, 14, 14, 14, 14, 14, 12, 12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6],
+        breath: [16, 16, 16, 16, 16, 16, 15, 15, 15, 15, 15, 14, 14, 14, 14, 13, 13, 13, 13, 12],
+        magic: [15, 15, 15, 15, 15, 15, 13, 13, 13, 13, 13, 11, 11, 11, 11, 9, 9, 9, 9, 7],
+    };
+    const ASSASSIN : Saves = Saves {
+        poison: [13, 13, 13, 13, 13, 13, 12, 12, 12, 12, 12, 11, 11, 11, 11, 10, 10, 10, 10, 9],
+        para: [12, 12, 12, 12, 12, 12, 11, 11, 11, 11, 11, 10, 10, 10, 10, 9, 9, 9, 9, 8],
+        poly: [14, 14, 14, 14, 14, 14, 13, 13, 13, 13, 13, 12, 12, 12, 12, 11, 11, 11, 11, 10],
+        rsw: [14, 14, 14, 14, 14, 14, 12, 12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6],
+        breath: [16, 16, 16, 16, 16, 16, 15, 15, 15, 15, 15, 14, 14, 14, 14, 13, 13, 13, 13, 12],
+        magic: [15, 15, 15, 15, 15, 15, 13, 13, 13, 13, 13, 11, 11, 11, 11, 9, 9, 9, 9, 7],
+    };
+    const MONK : Saves = Saves {
+        poison: [14, 14, 14, 14, 13, 13, 13, 13, 11, 11, 11, 11, 10, 10, 10, 10, 10, 8, 8, 8],
+        para: [12, 12, 12, 12, 11, 11, 11, 11, 10, 10, 10, 10, 9, 9, 9, 9, 9, 8, 8, 8],
+        poly: [12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6, 6, 6, 6, 6, 4, 4, 4],
+        rsw: [11, 11, 11, 11, 9, 9, 9, 9, 7, 7, 7, 7, 5, 5, 5, 5, 5, 3, 3, 3],
+        breath: [15, 15, 15, 15, 13, 13, 13, 13, 11, 11, 11, 11, 9, 9, 9, 9, 9, 7, 7, 7],
+        magic: [12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6, 6, 6, 6, 6, 4, 4, 4],
+    };
+    const BARD : Saves = Saves {
+        poison: [15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 11, 11, 11, 11, 11, 9, 9, 9, 9, 9],
+        para: [15, 15, 15, 14, 14, 14, 14, 13, 13, 13, 12, 12, 12, 12, 12, 10, 10, 10, 10, 10],
+        poly: [16, 16, 16, 15, 15, 15, 15, 13, 13, 13, 11, 11, 11, 11, 11, 10, 10, 10, 10, 10],
+        rsw: [15, 15, 15, 13, 13, 13, 13, 12, 12, 12, 10, 10, 10, 10, 10, 9, 9, 9, 9, 9],
+        breath: [17, 17, 17, 16, 16, 16, 16, 14, 14, 14, 13, 13, 13, 13, 13, 11, 11, 11, 11, 11],
+        magic: [15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 11, 11, 11, 11, 11, 9, 9, 9, 9, 9],
+    };
+    const MONSTER : Saves = Saves {
+        poison: [15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15],
+        para: [15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15],
+        poly: [16, 16, 16, 15, 15, 15, 15, 13, 13, 13, 11, 11, 11, 11, 11, 10, 10, 10, 10, 10],
+        rsw: [15, 15, 15, 13, 13, 13, 13, 12, 12, 12, 10, 10, 10, 10, 10, 9, 9, 9, 9, 9],
+        breath: [17, 17, 17, 16, 16, 16, 16, 14, 14, 14, 13, 13, 13, 13, 13, 11, 11, 11, 11, 11],
+        magic: [15, 15, 15, 14, 14, 14, 14, 12, 12, 12, 11, 11, 11, 11, 11, 9, 9, 9, 9, 9],
+    };
+    const MAGICAL : Saves = Saves {
+        poison: [14, 14, 14, 14, 13, 13, 13, 13, 11, 11, 11, 11, 10, 10, 10, 10, 10, 8, 8, 8],
+        para: [12, 12, 12, 12, 11, 11, 11, 11, 10, 10, 10, 10, 9, 9, 9, 9, 9, 8, 8, 8],
+        poly: [12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6, 6, 6, 6, 6, 4, 4, 4],
+        rsw: [11, 11, 11, 11, 9, 9, 9, 9, 7, 7, 7, 7, 5, 5, 5, 5, 5, 3, 3, 3],
+        breath: [15, 15, 15, 15, 13, 13, 13, 13, 11, 11, 11, 11, 9, 9, 9, 9, 9, 7, 7, 7],
+        magic: [12, 12, 12, 12, 10, 10, 10, 10, 8, 8, 8, 8, 6, 6, 6, 6, 6, 4, 4, 4],
+    };
+
+    /// Return the target for the given category at the given level.
+    fn target(&self, cat: SaveCategory, lvl: u32) -> u32 {
+        let idx = if lvl <= 1 {
+            0usize
+        } else {
+            (lvl as usize - 1).min(self.poison.len() - 1)
+        };
+        match cat {
+            SaveCategory::Poison => self.poison[idx],
+            SaveCategory::Para => self.para[idx],
+            SaveCategory::Poly => self.poly[idx],
+            SaveCategory::Rsw => self.rsw[idx],
+            SaveCategory::Breath => self.breath[idx],
+            SaveCategory::Magic => self.magic[idx],
+        }
+    }
+}
+
+/// The categories of saving throw.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SaveCategory {
+    /// Poison or death magic
+    Poison,
+    /// Paralyzation
+    Para,
+    /// Petrification or polymorph
+    Poly,
+    /// Rod, staff or wand
+    Rsw,
+    /// Breath weapon
+    Breath,
+    /// Spell
+    Magic,
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid saving throw category")]
+pub struct ParseSaveCategoryError;
+
+impl FromStr for SaveCategory {
+    type Err = ParseSaveCategoryError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "poison" | "death" | "po" => Ok(SaveCategory::Poison),
+            "para" | "paralyzation" | "pa" => Ok(SaveCategory::Para),
+            "poly" | "petrification" | "pe" => Ok(SaveCategory::Poly),
+            "rsw" | "rod" | "staff" | "wand" | "r" => Ok(SaveCategory::Rsw),
+            "breath" | "b" => Ok(SaveCategory::Breath),
+            "magic" | "spell" | "m" => Ok(SaveCategory::Magic),
+            _ => Err(ParseSaveCategoryError),
+        }
+    }
+}
+
+impl fmt::Display for SaveCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            SaveCategory::Poison => "poison",
+            SaveCategory::Para => "paralyzation",
+            SaveCategory::Poly => "petrification",
+            SaveCategory::Rsw => "rod/staff/wand",
+            SaveCategory::Breath => "breath weapon",
+            SaveCategory::Magic => "spell",
+        })
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Abilities {
     #[serde(rename = "str")]
     strength: u32,
-    //#[serde(rename = "str2")]
-    //exceptional_str: Option<u32>,
+    /// Percentile strength for scores of 18, where 100 represents 18/00.
+    #[serde(rename = "str2")]
+    exceptional_str: Option<u32>,
     #[serde(rename = "int")]
     intelligence: u32,
     #[serde(rename = "wis")]
@@ -318,6 +933,8 @@ pub enum ParseAbilitiesError {
     Int(#[cause] ParseIntError),
     #[fail(display = "Invalid number of ability fields")]
     NumArgs,
+    #[fail(display = "Exceptional strength must be 18/01 to 18/00")]
+    ExceptionalStr,
 }
 
 impl From<ParseIntError> for ParseAbilitiesError {
@@ -332,20 +949,146 @@ impl FromStr for Abilities {
         let terms : Result<Vec<u32>, ParseAbilitiesError> = s.split('/')
             .map(|s| s.parse::<u32>().map_err(|e| e.into()))
             .collect();
-        terms.and_then(|v| {
+        terms.and_then(|mut v| {
+            // exceptional strength is given as a seventh term: 18/75/...
+            let exceptional_str = if v.len() == 7 {
+                // 18/00 is the highest exceptional strength
+                match (v[0], v.remove(1)) {
+                    (18, 0) => Some(100),
+                    (18, e) if e <= 100 => Some(e),
+                    _ => return Err(ParseAbilitiesError::ExceptionalStr),
+                }
+            } else {
+                None
+            };
             if v.len() == 6 {
-                Ok(Abilities { strength: v[0], intelligence: v[1], wisdom: v[2],
-                    dexterity: v[3], constitution: v[4], charisma: v[5]})
+                Ok(Abilities { strength: v[0], exceptional_str, intelligence: v[1],
+                    wisdom: v[2], dexterity: v[3], constitution: v[4], charisma: v[5]})
             } else {
                 Err(ParseAbilitiesError::NumArgs)
         }})
     }
 }
 
+impl Abilities {
+    // Modifiers by ability score, from 3 up to 18 or more
+    const DEX_INIT_MOD : [i32; 16] = [-3, -2, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3];
+    const DEX_AC_MOD : [i32; 16] = [4, 3, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0, -1, -2, -3, -4];
+    const DEX_MISSILE_MOD : [i32; 16] = [-3, -2, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3];
+    const WIS_SAVE_BONUS : [i32; 16] = [-3, -2, -1, -1, -1, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4];
+    const CHA_REACTION_ADJ : [i32; 16] = [-30, -25, -20, -15, -10, -5, 0, 0, 0, 0, 5, 10, 15, 25, 30, 35];
+    const CON_HP_BONUS : [i32; 16] = [-2, -1, -1, -1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 2, 2];
+
+    /// Look up the modifier for the given score in a table starting at 3.
+    fn lookup(table: &[i32; 16], score: u32) -> i32 {
+        table[(score.clamp(3, 18) - 3) as usize]
+    }
+
+    /// Create a set of scores without exceptional strength.
+    const fn scores(strength: u32, intelligence: u32, wisdom: u32,
+                    dexterity: u32, constitution: u32, charisma: u32) -> Self {
+        Abilities { strength, exceptional_str: None, intelligence, wisdom,
+            dexterity, constitution, charisma }
+    }
+
+    pub fn dexterity(&self) -> u32 {
+        self.dexterity
+    }
+
+    /// Return true if the scores are high enough for the class
+    /// to earn a 10% xp bonus.
+    pub fn qualifies_for_xp_bonus(&self, class: Class) -> bool {
+        self.meets(&class.record().xp)
+    }
+
+    /// Return true if every score is at least that of the given requirements.
+    fn meets(&self, req: &Abilities) -> bool {
+        self.strength >= req.strength
+            && self.intelligence >= req.intelligence
+            && self.wisdom >= req.wisdom
+            && self.dexterity >= req.dexterity
+            && self.constitution >= req.constitution
+            && self.charisma >= req.charisma
+    }
+
+    /// Return the reaction/initiative adjustment granted by dexterity.
+    pub fn dex_initiative_mod(&self) -> i32 {
+        Abilities::lookup(&Abilities::DEX_INIT_MOD, self.dexterity)
+    }
+
+    /// Return the armour class adjustment granted by dexterity.
+    /// Negative values improve armour class.
+    pub fn dex_ac_mod(&self) -> i32 {
+        Abilities::lookup(&Abilities::DEX_AC_MOD, self.dexterity)
+    }
+
+    /// Return the to-hit modifier for missile attacks granted by dexterity.
+    pub fn dex_missile_mod(&self) -> i32 {
+        Abilities::lookup(&Abilities::DEX_MISSILE_MOD, self.dexterity)
+    }
+
+    /// Return the saving throw bonus against magical attacks granted by wisdom.
+    pub fn wis_saving_throw_bonus(&self) -> i32 {
+        Abilities::lookup(&Abilities::WIS_SAVE_BONUS, self.wisdom)
+    }
+
+    /// Return the percentage reaction adjustment granted by charisma.
+    pub fn cha_reaction_adj(&self) -> i32 {
+        Abilities::lookup(&Abilities::CHA_REACTION_ADJ, self.charisma)
+    }
+
+    /// Return the hit point adjustment per hit die granted by constitution.
+    pub fn con_hp_bonus(&self) -> i32 {
+        Abilities::lookup(&Abilities::CON_HP_BONUS, self.constitution)
+    }
+
+    /// Return strength as a single value on the percentile scale,
+    /// where 18/01 through 18/00 map to 1801 through 1900.
+    fn percentile_str(&self) -> u32 {
+        match (self.strength, self.exceptional_str) {
+            (18, Some(e)) => 1800 + e.min(100),
+            (s, _) if s > 18 => 1900,
+            (s, _) => s * 100,
+        }
+    }
+
+    /// Return the to-hit modifier granted by strength.
+    pub fn str_hit_mod(&self) -> i32 {
+        match self.percentile_str() {
+            0..=300 => -3,
+            301..=500 => -2,
+            501..=700 => -1,
+            701..=1600 => 0,
+            1601..=1850 => 1,
+            1851..=1899 => 2,
+            _ => 3,
+        }
+    }
+
+    /// Return the damage modifier granted by strength.
+    pub fn str_dmg_mod(&self) -> i32 {
+        match self.percentile_str() {
+            0..=500 => -1,
+            501..=1500 => 0,
+            1501..=1700 => 1,
+            1701..=1800 => 2,
+            1801..=1875 => 3,
+            1876..=1890 => 4,
+            1891..=1899 => 5,
+            _ => 6,
+        }
+    }
+}
+
 impl fmt::Display for Abilities {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "STR: {}\n\rINT: {}\n\rWIS: {}\n\rDEX: {}\n\rCON: {}\n\rCHA: {}",
-               self.strength, self.intelligence, self.wisdom, self.dexterity,
+        let strength = match self.exceptional_str {
+            Some(100) => format!("{}/00", self.strength),
+            Some(e) => format!("{}/{:02}", self.strength, e),
+            None => self.strength.to_string(),
+        };
+        write!(f, "STR: {}\nINT: {}\nWIS: {}\nDEX: {}\nCON: {}\nCHA: {}",
+               strength, self.intelligence, self.wisdom, self.dexterity,
                self.constitution, self.charisma)
     }
 }
@@ -377,7 +1120,7 @@ impl fmt::Display for Abilities {
 //     }
 // }
 
-#[derive(Fail, Debug)]
+#[derive(Fail, Debug, PartialEq)]
 pub enum CombatError {
     #[fail(display = "Not enough attacks left")]
     NotEnoughAttacks,
@@ -385,6 +1128,10 @@ pub enum CombatError {
     NotInCombat,
     #[fail(display = "Target not initialized")]
     NotBuilt,
+    #[fail(display = "No spell slots left at that level")]
+    NoSpellSlots,
+    #[fail(display = "HP must be positive")]
+    InvalidHP,
 }
 
 impl Combatant {
@@ -418,23 +1165,58 @@ impl Combatant {
         self.name = name.into();
     }
 
+    /// Advance one level and recalculate hd, THAC0 and spell slots.
+    /// Slots already spent stay spent at the new level.
+    ///
+    /// Characters stop at level 13, the end of the THAC0 tables. Monsters keep
+    /// gaining hit dice, since many monsters have well over 13 HD; the THAC0,
+    /// saving throw and xp tables hold at their last entry for them.
+    pub fn level_up(&mut self) {
+        self.class.increment_lvl();
+        self.hd = self.class.level();
+        self.thac0 = self.class.thac0();
+        let old = self.spell_slots.take().unwrap_or_default();
+        self.spell_slots = self.class.default_spell_slots(self.class.level())
+            .map(|v| v.into_iter().enumerate().map(|(i, n)| {
+                let spent = old.get(i).map(|m| m.max() - m.curr()).unwrap_or(0);
+                Meter::new(n.saturating_sub(spent), n)
+            }).collect());
+    }
+
     pub fn update(&mut self) {
         self.round += 1;
-        if let Status::Stunned(_) = self.status {
-            // revert to healthy
-            self.status = Status::Healthy;
-        }
+        self.per_round_dealt = 0;
+        self.per_round_recvd = 0;
+        self.status = match self.status {
+            // count down the stun, reverting to healthy when it runs out;
+            // unconscious combatants stay down until revived
+            Status::Stunned(x) if x > 1 => Status::Stunned(x - 1),
+            Status::Stunned(_) => Status::Healthy,
+            s => s,
+        };
         // refill attacks
         self.attacks += self.attacks.max();
     }
 
-    /// Calculate initiative relative to base initiative and current state.
+    /// Calculate initiative relative to base initiative, dexterity and current state.
+    /// Spectators always have an initiative of 0.
     pub fn get_init(&self) -> u32 {
-        match self.status {
+        if self.spectator {
+            return 0;
+        }
+        let base = match self.status {
             Status::Healthy => self.init + Combatant::INIT_MOD * 2,
             Status::Stunned(x) => self.init + Combatant::INIT_MOD - x,
-            Status::Dead => 0,
-        }
+            Status::Unconscious | Status::Dead => return 0,
+        };
+        let dex_mod = self.abilities.map(|a| a.dex_initiative_mod()).unwrap_or(0);
+        // never let a dexterity penalty take a living combatant to 0
+        (base as i32 + dex_mod).max(1) as u32
+    }
+
+    /// Return the combatant's dexterity score, or 0 if no abilities are set.
+    pub fn dex(&self) -> u32 {
+        self.abilities.map(|a| a.dexterity()).unwrap_or(0)
     }
 
     fn dead(&self) -> i32 {
@@ -445,65 +1227,172 @@ impl Combatant {
     }
 
     /// Return true if considered "in combat".
-    /// Equivalent to having HP, attacks, a team and initiative set.
+    /// Equivalent to having HP, a team and initiative set, where 0 means unset.
+    /// Spectators are never in combat.
     pub fn in_combat(&self) -> bool {
-        true
-        //self.hp.is_some() && self.attacks.is_some() && self.init.is_some() && self.team.is_some()
+        !self.spectator && self.hp.max() != 0 && self.team != 0 && self.init != 0
     }
 
     /// Return true if able to attack.
-    /// Must have attacks to spend.
+    /// Must be conscious and have attacks to spend.
     pub fn can_attack(&self) -> bool {
         //self.attacks.map(|a| a.curr() >= 1).unwrap_or(false)
-        self.attacks.curr() >= 1
+        self.status < Status::Unconscious && self.attacks.curr() >= 1
+    }
+
+    /// Return the roll needed to make a saving throw of the given category.
+    pub fn save_target(&self, cat: SaveCategory) -> u32 {
+        self.class.save_target(cat)
+    }
+
+    /// Return true if the given roll makes the saving throw.
+    pub fn saves(&self, cat: SaveCategory, roll: i32) -> bool {
+        roll >= self.save_target(cat) as i32
+    }
+
+    /// Return armour class adjusted for dexterity.
+    pub fn effective_ac(&self) -> i32 {
+        self.ac + self.abilities.map(|a| a.dex_ac_mod()).unwrap_or(0)
+    }
+
+    /// Return true if the given d20 roll hits the given armour class.
+    /// A natural 20 always hits and a natural 1 always misses.
+    pub fn hits(&self, roll: u32, target_ac: i32) -> bool {
+        match roll {
+            20 => true,
+            1 => false,
+            _ => roll >= (self.thac0 as i32 - target_ac).max(0) as u32,
+        }
     }
 
     /// Add to xp earnings for dealing a hit.
     pub fn deal_hit(&mut self, dam: i32) {
         self.dealt += dam;
-        self.attacks -= 1;
+        self.per_round_dealt += dam;
+        self.attacks = self.attacks.saturating_sub(1);
         // TODO: missing some way of allowing for 1 extra hit every X rounds
     }
 
-    /// Damage self.
-    pub fn recv_hit(&mut self, dam: i32) {
+    /// Damage self, returning the new status if it changed.
+    pub fn recv_hit(&mut self, dam: i32) -> Option<Status> {
+        let old = self.status;
         self.recvd += dam;
+        self.per_round_recvd += dam;
+        // temporary hp soak up as much of the damage as they can
+        let absorbed = dam.min(self.temp_hp.curr()).max(0);
+        self.temp_hp -= absorbed;
+        let dam = dam - absorbed;
         self.status = match self.status {
-            Status::Healthy | Status::Stunned(_) if (self.hp.curr() - dam <= self.dead()) => Status::Dead,
+            Status::Healthy | Status::Stunned(_) | Status::Unconscious
+                if (self.hp.curr() - dam <= self.dead()) => Status::Dead,
+            Status::Healthy | Status::Stunned(_) if (self.hp.curr() - dam <= 0) => Status::Unconscious,
             // if the current stun is bigger, retain it
             s @ Status::Healthy | s @ Status::Stunned(_) => {
                 let new = Status::stun_lock(dam, self.hp.curr());
                 if new > s {
                     // decrement attacks available on a new greater stun
                     if let Status::Stunned(x) = new {
-                        self.attacks -= x.min(self.attacks.curr());
+                        self.attacks = self.attacks.saturating_sub(x);
                     }
                     new
                 } else {
                     s
                 }
             },
-            s @ _ => s,
+            s => s,
         };
         self.hp -= dam;
+        if self.status != old {
+            Some(self.status)
+        } else {
+            None
+        }
     }
 
-    /// Heal self.
-    pub fn heal(&mut self, dam: i32) {
+    /// Heal self, returning the new status if healing wakes an unconscious combatant.
+    /// Temporary hp are not restored.
+    pub fn heal(&mut self, dam: i32) -> Option<Status> {
         self.hp += dam;
+        if self.status == Status::Unconscious && self.hp.curr() > 0 {
+            self.status = Status::Healthy;
+            Some(self.status)
+        } else {
+            None
+        }
+    }
+
+    /// Replace any temporary hp with the given amount.
+    pub fn grant_temp_hp(&mut self, amount: i32) -> Result<(), CombatError> {
+        if amount <= 0 {
+            return Err(CombatError::InvalidHP);
+        }
+        self.temp_hp = Meter::new(amount, amount);
+        Ok(())
     }
 
-    /// Reset combatant's damage dealt, damage received and round.
-    pub fn reset(&mut self) {
+    /// Bring a combatant back to healthy with the given hp and full attacks.
+    pub fn revive(&mut self, hp: i32) -> Result<(), CombatError> {
+        if hp <= 0 {
+            return Err(CombatError::InvalidHP);
+        }
+        self.status = Status::Healthy;
+        self.hp = Meter::new(hp.min(self.hp.max()), self.hp.max());
+        self.attacks += self.attacks.max();
+        Ok(())
+    }
+
+    /// Restore full hp and attacks and clear any stun, unconsciousness or death.
+    pub fn restore(&mut self) {
+        self.hp = Meter::new(self.hp.max(), self.hp.max());
+        self.status = Status::Healthy;
+        self.attacks = Meter::new(self.attacks.max(), self.attacks.max());
+    }
+
+    /// Reset combatant's damage dealt, damage received and round as well as hp,
+    /// attacks and status.
+    pub fn full_reset(&mut self) {
+        self.reset_stats();
+        self.restore();
+    }
+
+    /// Reset combatant's damage dealt, damage received and round, and refill spell slots.
+    pub fn reset_stats(&mut self) {
+        self.temp_hp = Meter::default();
         self.dealt = 0;
         self.recvd = 0;
+        self.per_round_dealt = 0;
+        self.per_round_recvd = 0;
         self.round = 1;
+        if let Some(ref mut slots) = self.spell_slots {
+            for slot in slots.iter_mut() {
+                *slot += slot.max();
+            }
+        }
+    }
+
+    /// Spend a spell slot of the given spell level.
+    pub fn cast(&mut self, lvl: usize) -> Result<(), CombatError> {
+        let slot = self.spell_slots.as_mut()
+            .and_then(|s| s.get_mut(lvl.wrapping_sub(1)))
+            .ok_or(CombatError::NoSpellSlots)?;
+        if slot.curr() == 0 {
+            return Err(CombatError::NoSpellSlots);
+        }
+        *slot = slot.saturating_sub(1);
+        Ok(())
     }
 
     /// Calculate xp earned.
+    /// Monsters award a fixed value based on their hit dice instead.
     pub fn xp(&self, team_bonus: i32) -> i32 {
-        ((self.dealt * 10 + self.recvd * 20 + team_bonus) as f64 
-            * if self.xp_bonus { 1.1 } else { 1.0 }) as i32
+        if let Classes::Monster { .. } = self.class {
+            return self.class.base_xp();
+        }
+        // xp = (dealt * 10 + recvd * 20) * (1.1 with a prime requisite bonus, else 1.0)
+        //      + team_bonus
+        // the bonus only rewards the combatant's own part, not the shared team award
+        let personal = self.dealt * 10 + self.recvd * 20;
+        (personal as f64 * if self.xp_bonus { 1.1 } else { 1.0 }) as i32 + team_bonus
     }
 
     /// Calculate xp earned for damage taken.
@@ -513,8 +1402,25 @@ impl Combatant {
 
     /// Return a detailed description of the Combatant's features.
     pub fn describe(&self) -> String {
-        format!("{}, {}\n\r{}", self.name, self.class,
-                self.abilities.map(|a| a.to_string()).unwrap_or("".into()))
+        let mut lines = vec![
+            format!("{}, {}", self.name, self.class),
+            format!("HD: {}  HP: {}  Temp HP: {}  AC: {}  THAC0: {}",
+                    self.hd, self.hp, self.temp_hp, self.ac, self.thac0),
+            format!("Status: {}  Attacks: {}", self.status.describe(), self.attacks),
+            format!("Stats: dealt {} / recvd {}  This round: dealt {} / recvd {}",
+                    self.dealt, self.recvd, self.per_round_dealt, self.per_round_recvd),
+        ];
+        if let Some(ref v) = self.spell_slots {
+            let slots = v.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(" ");
+            lines.push(format!("Spells: {}", slots));
+        }
+        if !self.notes.is_empty() {
+            lines.push(format!("Notes: {}", self.notes.chars().take(24).collect::<String>()));
+        }
+        if let Some(a) = self.abilities {
+            lines.push(a.to_string());
+        }
+        lines.join("\n")
     }
 }
 
@@ -523,9 +1429,11 @@ impl Combatant {
 pub enum Status {
     Healthy,
     Stunned(u32),
+    Unconscious,
     Dead,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for Status {
     fn default() -> Self {
         Status::Healthy
@@ -533,8 +1441,190 @@ impl Default for Status {
 }
 
 impl Status {
+    /// Return a readable description of the status.
+    pub fn describe(&self) -> String {
+        match *self {
+            Status::Healthy => "healthy".into(),
+            Status::Stunned(x) => format!("stunned ({} rounds)", x),
+            Status::Unconscious => "unconscious".into(),
+            Status::Dead => "dead".into(),
+        }
+    }
+
+    /// Stun thresholds as `(dam_mul, hp_mul, rounds)`, checked in order:
+    /// a hit stuns for `rounds` when `dam * dam_mul >= hp * hp_mul`, i.e. when it deals
+    /// at least `hp_mul / dam_mul` of the current hp. The fractions run from 6/7 down
+    /// to 1/4, so bigger hits relative to remaining hp stun for longer. These are a
+    /// house rule of this tracker rather than a table from the AD&D books.
+    const STUN_THRESHOLDS : &[(i32, i32, u32)] = &[
+        (7, 6, 8),  // 6/7 of hp
+        (6, 5, 7),  // 5/6
+        (5, 4, 6),  // 4/5
+        (4, 3, 5),  // 3/4
+        (3, 2, 4),  // 2/3
+        (2, 1, 3),  // 1/2
+        (3, 1, 2),  // 1/3
+        (4, 1, 1),  // 1/4
+    ];
+
     /// Calculate stun lock effect based on damage versus hp.
     fn stun_lock(dam: i32, hp: i32) -> Self {
+        Status::STUN_THRESHOLDS.iter()
+            .find(|&&(dam_mul, hp_mul, _)| dam * dam_mul >= hp * hp_mul)
+            .map(|&(_, _, rounds)| Status::Stunned(rounds))
+            .unwrap_or(Status::Healthy)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Status::Dead => write!(f, "#"),
+            Status::Unconscious => write!(f, "~"),
+            Status::Stunned(x) => write!(f, "*{}", x),
+            Status::Healthy => write!(f, "+"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return a level 1 fighter builder with every field set.
+    fn fighter() -> CombatantBuilder {
+        CombatantBuilder::new("Fighter")
+            .class(Classes::Single { name: Class::Fighter, lvl: 1 })
+            .hd(1u32)
+            .hp(Meter::new(10, 10))
+            .attacks(Meter::new(1u32, 1))
+            .ac(5)
+            .team(1u32)
+            .init(5u32)
+    }
+
+    #[test]
+    fn build_rejects_zero_hp() {
+        assert_eq!(fighter().hp(Meter::new(0, 0)).build().unwrap_err(), BuildError::ZeroHP);
+    }
+
+    #[test]
+    fn build_rejects_zero_team() {
+        assert_eq!(fighter().team(0u32).build().unwrap_err(), BuildError::ZeroTeam);
+    }
+
+    #[test]
+    fn build_rejects_zero_init() {
+        assert_eq!(fighter().init(0u32).build().unwrap_err(), BuildError::ZeroInit);
+    }
+
+    #[test]
+    fn in_combat_requires_set_fields() {
+        let c = fighter().build().unwrap();
+        assert!(c.in_combat());
+        let mut no_hp = c.clone();
+        no_hp.hp = Meter::new(0, 0);
+        assert!(!no_hp.in_combat());
+        let mut no_team = c.clone();
+        no_team.team = 0;
+        assert!(!no_team.in_combat());
+        let mut no_init = c.clone();
+        no_init.init = 0;
+        assert!(!no_init.in_combat());
+        let mut spectator = c;
+        spectator.spectator = true;
+        assert!(!spectator.in_combat());
+    }
+
+    #[test]
+    fn level_up_raises_monster_past_table() {
+        let mut c = fighter().class(Classes::Monster { magical: false, hd_num: 16, hd_den: 1 })
+            .hd(16u32).build().unwrap();
+        c.level_up();
+        assert_eq!(c.class.level(), 17);
+        assert_eq!(c.hd, 17);
+        assert_eq!(c.thac0, 8);
+    }
+
+    #[test]
+    fn level_up_caps_characters() {
+        let mut c = fighter().class(Classes::Single { name: Class::Fighter, lvl: 13 })
+            .hd(13u32).build().unwrap();
+        c.level_up();
+        assert_eq!(c.class.level(), 13);
+        assert_eq!(c.hd, 13);
+        assert_eq!(c.thac0, 8);
+    }
+
+    #[test]
+    fn level_up_raises_monster_hd_without_limit() {
+        let mut c = fighter().class(Classes::Monster { magical: false, hd_num: 1, hd_den: 2 })
+            .build().unwrap();
+        // half a hit die rises to a whole one
+        c.level_up();
+        assert_eq!((c.hd, c.thac0), (1, 20));
+        for _ in 0..20 {
+            c.level_up();
+        }
+        assert_eq!(c.hd, 21);
+        assert_eq!(c.thac0, 8);
+    }
+
+    #[test]
+    fn level_up_updates_hd_and_spell_slots() {
+        let mut c = fighter().class(Classes::Single { name: Class::Mage, lvl: 2 })
+            .hd(2u32).build().unwrap();
+        c.cast(1).unwrap();
+        assert_eq!(c.spell_slots, Some(vec![Meter::new(1, 2)]));
+        c.level_up();
+        assert_eq!(c.hd, 3);
+        assert_eq!(c.thac0, 21);
+        // the spent 1st level slot stays spent
+        assert_eq!(c.spell_slots, Some(vec![Meter::new(1, 2), Meter::new(1, 1)]));
+    }
+
+    /// Return the THAC0 of the given class at levels 1, 6 and 12.
+    fn thac0s(class: Classes) -> [u32; 3] {
+        [class.clone().lvl(1).thac0(), class.clone().lvl(6).thac0(), class.lvl(12).thac0()]
+    }
+
+    #[test]
+    fn thac0_by_progression() {
+        let single = |c| Classes::Single { name: c, lvl: 1 };
+        assert_eq!(thac0s(single(Class::Cleric)), [20, 17, 13]);
+        assert_eq!(thac0s(single(Class::Fighter)), [20, 15, 9]);
+        assert_eq!(thac0s(single(Class::Mage)), [21, 19, 17]);
+        assert_eq!(thac0s(single(Class::Thief)), [21, 19, 16]);
+    }
+
+    #[test]
+    fn multi_class_thac0_uses_best_progression() {
+        let multi = |v| Classes::Multi { name: v, lvl: 1 };
+        assert_eq!(thac0s(multi(vec![Class::Fighter, Class::Mage])), [20, 15, 9]);
+        assert_eq!(thac0s(multi(vec![Class::Mage, Class::Thief])), [21, 19, 16]);
+    }
+
+    #[test]
+    fn dex_and_con_tables_clamp_to_3_through_18() {
+        let dex = |d: u32| format!("10/10/10/{}/10/10", d).parse::<Abilities>().unwrap().dex_initiative_mod();
+        let con = |c: u32| format!("10/10/10/10/{}/10", c).parse::<Abilities>().unwrap().con_hp_bonus();
+        assert_eq!([0, 3, 4, 5, 6, 15, 16, 17, 18, 25].iter().map(|&d| dex(d)).collect::<Vec<_>>(),
+                   vec![-3, -3, -2, -1, 0, 0, 1, 2, 3, 3]);
+        assert_eq!([0, 3, 4, 6, 7, 14, 15, 16, 18, 25].iter().map(|&c| con(c)).collect::<Vec<_>>(),
+                   vec![-2, -2, -1, -1, 0, 0, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn hit_below_zero_hp_knocks_out_without_stun() {
+        let mut c = fighter().build().unwrap();
+        c.hp = Meter::new(-1, 10);
+        assert_eq!(c.recv_hit(5), Some(Status::Unconscious));
+        assert_eq!(c.attacks.curr(), 1);
+        assert_eq!(c.hp.curr(), -6);
+    }
+
+    /// The stun lock rules as originally written, for checking the threshold table.
+    fn chained_stun_lock(dam: i32, hp: i32) -> Status {
         if dam * 7 >= hp * 6 {
             Status::Stunned(8)
         } else if dam * 6 >= hp * 5 {
@@ -555,14 +1645,160 @@ impl Status {
             Status::Healthy
         }
     }
-}
 
-impl fmt::Display for Status {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", match *self {
-            Status::Dead => "#",
-            Status::Stunned(_) => "*",
-            Status::Healthy => "+",
-        })
+    #[test]
+    fn stun_lock_matches_chained_rules() {
+        for hp in 1..=60 {
+            for dam in 0..=hp + 10 {
+                assert_eq!(Status::stun_lock(dam, hp), chained_stun_lock(dam, hp),
+                           "{} damage against {} hp", dam, hp);
+            }
+        }
+        let at_12 = |dam| Status::stun_lock(dam, 12);
+        assert_eq!((2..=11).map(at_12).collect::<Vec<_>>(), vec![
+            Status::Healthy, Status::Stunned(1), Status::Stunned(2), Status::Stunned(2),
+            Status::Stunned(3), Status::Stunned(3), Status::Stunned(4), Status::Stunned(5),
+            Status::Stunned(7), Status::Stunned(8),
+        ]);
+    }
+
+    #[test]
+    fn xp_bonus_only_applies_to_personal_xp() {
+        let strong = "16/10/10/10/10/10".parse::<Abilities>().unwrap();
+        let mut c = fighter().abilities(strong).build().unwrap();
+        assert!(c.xp_bonus);
+        c.dealt = 7;
+        c.recvd = 3;
+        // (7 * 10 + 3 * 20) * 1.1 + 50
+        assert_eq!(c.xp(50), 143 + 50);
+        c.xp_bonus = false;
+        assert_eq!(c.xp(50), 130 + 50);
+    }
+
+    #[test]
+    fn exceptional_str_needs_18() {
+        let a = |s: &str| s.parse::<Abilities>();
+        assert_eq!(a("18/00/10/10/10/10/10").unwrap().exceptional_str, Some(100));
+        assert_eq!(a("18/51/10/10/10/10/10").unwrap().exceptional_str, Some(51));
+        assert_eq!(a("18/51/10/10/10/10/10").unwrap().to_string().lines().next(), Some("STR: 18/51"));
+        for bad in &["16/50/10/10/10/10/10", "18/101/10/10/10/10/10"] {
+            match a(bad) {
+                Err(ParseAbilitiesError::ExceptionalStr) => (),
+                r => panic!("expected an exceptional strength error for {}, got {:?}", bad, r),
+            }
+        }
+    }
+
+    #[test]
+    fn str_mods_change_at_band_edges() {
+        let mods = |s: &str| {
+            let a = format!("{}/10/10/10/10/10", s).parse::<Abilities>().unwrap();
+            (a.str_hit_mod(), a.str_dmg_mod())
+        };
+        assert_eq!(mods("5"), (-2, -1));
+        assert_eq!(mods("6"), (-1, 0));
+        assert_eq!(mods("7"), (-1, 0));
+        assert_eq!(mods("8"), (0, 0));
+        assert_eq!(mods("15"), (0, 0));
+        assert_eq!(mods("16"), (0, 1));
+        assert_eq!(mods("17"), (1, 1));
+        assert_eq!(mods("18"), (1, 2));
+        assert_eq!(mods("18/01"), (1, 3));
+        assert_eq!(mods("18/50"), (1, 3));
+        assert_eq!(mods("18/51"), (2, 3));
+        assert_eq!(mods("18/75"), (2, 3));
+        assert_eq!(mods("18/76"), (2, 4));
+        assert_eq!(mods("18/90"), (2, 4));
+        assert_eq!(mods("18/91"), (2, 5));
+        assert_eq!(mods("18/99"), (2, 5));
+        assert_eq!(mods("18/00"), (3, 6));
+    }
+
+    #[test]
+    fn con_hp_bonus_keeps_1_hp_per_hit_die() {
+        let con = |c: u32| format!("10/10/10/10/{}/10", c).parse::<Abilities>().unwrap();
+        let hp = |c: u32, hd: u32, max: i32| {
+            fighter().abilities(con(c)).hd(hd).hp(Meter::new(max, max)).build().unwrap().hp
+        };
+        assert_eq!(hp(16, 2, 10), Meter::new(14, 14));
+        assert_eq!(hp(3, 2, 10), Meter::new(6, 6));
+        assert_eq!(hp(3, 1, 1), Meter::new(1, 1));
+        assert_eq!(hp(3, 3, 4), Meter::new(3, 3));
+        // an unset hp is still rejected, whatever the constitution
+        let unset = fighter().abilities(con(16)).hp(Meter::new(0, 0)).build();
+        assert_eq!(unset.unwrap_err(), BuildError::ZeroHP);
+    }
+
+    #[test]
+    fn class_records_match_classes_csv() {
+        for line in include_str!("../classes.csv").lines().skip(1) {
+            let cols = line.split(',').collect::<Vec<_>>();
+            // the monster rows have no class
+            let class = match cols[0].parse::<Class>() {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let n = cols[1..].iter().map(|x| x.parse::<u32>().unwrap()).collect::<Vec<_>>();
+            let scores = |i: usize| Abilities::scores(n[i], n[i + 1], n[i + 2], n[i + 3], n[i + 4], n[i + 5]);
+            let r = class.record();
+            assert_eq!(r.min, scores(0), "{} minimums", class);
+            assert_eq!(r.multi, scores(6), "{} multi-class minimums", class);
+            assert_eq!(r.xp, scores(12), "{} bonus xp scores", class);
+            assert_eq!(&r.thac0[..], &n[18..31], "{} THAC0", class);
+        }
+    }
+
+    #[test]
+    fn xp_bonus_follows_class_records() {
+        let a = |s: &str| s.parse::<Abilities>().unwrap();
+        assert!(a("16/10/10/10/10/10").qualifies_for_xp_bonus(Class::Fighter));
+        assert!(!a("15/10/10/10/10/10").qualifies_for_xp_bonus(Class::Fighter));
+        // druids need both wisdom and charisma
+        assert!(a("10/10/16/10/10/16").qualifies_for_xp_bonus(Class::Druid));
+        assert!(!a("10/10/18/10/10/15").qualifies_for_xp_bonus(Class::Druid));
+        // paladins and rangers need several scores
+        assert!(a("16/9/16/10/9/17").qualifies_for_xp_bonus(Class::Paladin));
+        assert!(!a("18/10/16/10/10/16").qualifies_for_xp_bonus(Class::Paladin));
+        assert!(a("16/16/16/10/14/10").qualifies_for_xp_bonus(Class::Ranger));
+        assert!(!a("16/15/16/10/14/10").qualifies_for_xp_bonus(Class::Ranger));
+        // some classes never earn the bonus
+        let best = a("18/18/18/18/18/18");
+        for &c in &[Class::Illusionist, Class::Assassin, Class::Monk, Class::Bard] {
+            assert!(!best.qualifies_for_xp_bonus(c), "{} earned bonus xp", c);
+        }
+    }
+
+    #[test]
+    fn healing_above_0_hp_wakes_the_unconscious() {
+        let mut c = fighter().build().unwrap();
+        assert_eq!(c.recv_hit(12), Some(Status::Unconscious));
+        assert_eq!(c.heal(1), None);
+        assert_eq!(c.status, Status::Unconscious);
+        assert_eq!(c.heal(3), Some(Status::Healthy));
+        assert_eq!(c.hp.curr(), 2);
+        // the dead stay dead
+        c.recv_hit(20);
+        assert_eq!(c.status, Status::Dead);
+        assert_eq!(c.heal(30), None);
+        assert_eq!(c.status, Status::Dead);
+    }
+
+    #[test]
+    fn temp_hp_absorb_hits_first() {
+        let mut c = fighter().build().unwrap();
+        c.grant_temp_hp(4).unwrap();
+        c.recv_hit(3);
+        assert_eq!((c.temp_hp.curr(), c.hp.curr()), (1, 10));
+        c.recv_hit(3);
+        assert_eq!((c.temp_hp.curr(), c.hp.curr()), (0, 8));
+    }
+
+    #[test]
+    fn temp_hp_must_be_positive() {
+        let mut c = fighter().build().unwrap();
+        c.grant_temp_hp(4).unwrap();
+        assert_eq!(c.grant_temp_hp(0), Err(CombatError::InvalidHP));
+        assert_eq!(c.grant_temp_hp(-2), Err(CombatError::InvalidHP));
+        assert_eq!(c.temp_hp, Meter::new(4, 4));
     }
 }
@@ -0,0 +1,206 @@
+//! Dice notation (e.g. "2d6+3") parsing and rolling.
+
+use meters::Meter;
+use rand::{self, Rng};
+use std::str::FromStr;
+use std::num::ParseIntError;
+
+/// A roll of `num` dice with `sides` sides each, plus a flat modifier.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Dice {
+    num: u32,
+    sides: u32,
+    modifier: i32,
+}
+
+#[derive(Debug, Fail)]
+pub enum DiceError {
+    #[fail(display = "Invalid integer value")]
+    Int(#[cause] ParseIntError),
+    #[fail(display = "Dice must have at least one side")]
+    NoSides,
+    #[fail(display = "Cannot roll more than {} dice", _0)]
+    TooManyDice(u32),
+    #[fail(display = "Dice cannot have more than {} sides", _0)]
+    TooManySides(u32),
+    #[fail(display = "Roll is too large")]
+    Overflow,
+    #[fail(display = "Amount must not be negative")]
+    Negative,
+}
+
+impl From<ParseIntError> for DiceError {
+    fn from(e: ParseIntError) -> Self {
+        DiceError::Int(e)
+    }
+}
+
+impl Dice {
+    /// Limits on the dice in one roll, keeping the total well within an i32.
+    const MAX_DICE : u32 = 100;
+    const MAX_SIDES : u32 = 1000;
+
+    /// Roll the dice using the given random number generator.
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> Result<i32, DiceError> {
+        let total = (0..self.num)
+            .try_fold(0i32, |acc, _| acc.checked_add(rng.gen_range(1, self.sides as i32 + 1)))
+            .ok_or(DiceError::Overflow)?;
+        total.checked_add(self.modifier).ok_or(DiceError::Overflow)
+    }
+}
+
+impl FromStr for Dice {
+    type Err = DiceError;
+    /// Parse NdM, NdM+K, NdM-K or a bare integer K.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (num, rest) = match s.find('d') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            // a bare integer is a roll of no dice
+            None => return Ok(Dice { num: 0, sides: 1, modifier: s.parse::<i32>()? }),
+        };
+        // "d6" is shorthand for "1d6"
+        let num = if num.is_empty() { 1 } else { num.parse::<u32>()? };
+        let (sides, modifier) = match rest.find(['+', '-']) {
+            Some(i) => (rest[..i].parse::<u32>()?, rest[i..].parse::<i32>()?),
+            None => (rest.parse::<u32>()?, 0),
+        };
+        if sides == 0 {
+            return Err(DiceError::NoSides);
+        }
+        if num > Dice::MAX_DICE {
+            return Err(DiceError::TooManyDice(Dice::MAX_DICE));
+        }
+        if sides > Dice::MAX_SIDES {
+            return Err(DiceError::TooManySides(Dice::MAX_SIDES));
+        }
+        Ok(Dice { num, sides, modifier })
+    }
+}
+
+/// Parse and roll the given dice notation using the given random number generator.
+pub fn roll_dice<R: Rng>(s: &str, rng: &mut R) -> Result<i32, DiceError> {
+    s.parse::<Dice>().and_then(|d| d.roll(rng))
+}
+
+/// Parse and roll an amount of damage or healing using the given random number generator.
+/// As in AD&D, a roll of dice always comes to at least 1,
+/// while a fixed amount must not be negative.
+pub fn roll_amount<R: Rng>(s: &str, rng: &mut R) -> Result<i32, DiceError> {
+    let d = s.parse::<Dice>()?;
+    let r = d.roll(rng)?;
+    if d.num > 0 {
+        Ok(r.max(1))
+    } else if r < 0 {
+        Err(DiceError::Negative)
+    } else {
+        Ok(r)
+    }
+}
+
+/// Parse and roll an amount of damage or healing.
+pub fn parse_amount(s: &str) -> Result<i32, DiceError> {
+    roll_amount(s, &mut rand::thread_rng())
+}
+
+/// Parse and roll the given dice notation.
+pub fn parse_dice(s: &str) -> Result<i32, DiceError> {
+    roll_dice(s, &mut rand::thread_rng())
+}
+
+/// Parse a meter whose terms may be given in dice notation.
+/// A single term is used as both the current and maximum values.
+pub fn parse_dice_meter(s: &str) -> Result<Meter<i32>, DiceError> {
+    let mut terms = s.splitn(2, '/');
+    let curr = parse_dice(terms.next().unwrap_or(""))?;
+    let max = match terms.next() {
+        Some(t) => parse_dice(t)?,
+        None => curr,
+    };
+    Ok(Meter::new(curr.min(max), max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(1262)
+    }
+
+    #[test]
+    fn parse_notation() {
+        assert_eq!("2d6+3".parse::<Dice>().unwrap(), Dice { num: 2, sides: 6, modifier: 3 });
+        assert_eq!("d8-1".parse::<Dice>().unwrap(), Dice { num: 1, sides: 8, modifier: -1 });
+        assert_eq!("4".parse::<Dice>().unwrap(), Dice { num: 0, sides: 1, modifier: 4 });
+    }
+
+    #[test]
+    fn bare_integer_rolls_itself() {
+        assert_eq!(roll_dice("-3", &mut rng()).unwrap(), -3);
+    }
+
+    #[test]
+    fn rolls_stay_in_range() {
+        let mut rng = rng();
+        for _ in 0..200 {
+            let r = roll_dice("2d6+3", &mut rng).unwrap();
+            assert!((5..=15).contains(&r), "{} out of range", r);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_rolls() {
+        let (mut a, mut b) = (rng(), rng());
+        let rolls_a = (0..10).map(|_| roll_dice("3d20", &mut a).unwrap()).collect::<Vec<_>>();
+        let rolls_b = (0..10).map(|_| roll_dice("3d20", &mut b).unwrap()).collect::<Vec<_>>();
+        assert_eq!(rolls_a, rolls_b);
+    }
+
+    #[test]
+    fn rejects_zero_sides() {
+        match "1d0".parse::<Dice>() {
+            Err(DiceError::NoSides) => (),
+            r => panic!("expected NoSides, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_dice() {
+        match "1000000000d6".parse::<Dice>() {
+            Err(DiceError::TooManyDice(_)) => (),
+            r => panic!("expected TooManyDice, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_sides() {
+        match "1d4294967295".parse::<Dice>() {
+            Err(DiceError::TooManySides(_)) => (),
+            r => panic!("expected TooManySides, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn modifier_overflow_is_an_error() {
+        match roll_dice("1d6+2147483647", &mut rng()) {
+            Err(DiceError::Overflow) => (),
+            r => panic!("expected Overflow, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn amounts_are_never_negative() {
+        let mut rng = rng();
+        for _ in 0..20 {
+            assert_eq!(roll_amount("1d4-5", &mut rng).unwrap(), 1);
+        }
+        assert_eq!(roll_amount("0", &mut rng).unwrap(), 0);
+        match roll_amount("-3", &mut rng) {
+            Err(DiceError::Negative) => (),
+            r => panic!("expected a negative amount error, got {:?}", r),
+        }
+    }
+}
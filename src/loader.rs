@@ -0,0 +1,182 @@
+//! Loading of prepared combatant rosters.
+
+use combatants::{Abilities, Class, Classes, CombatantBuilder};
+use meters::Meter;
+use failure::Error;
+use serde_json;
+use BattleRow;
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// A simplified description of a combatant, as written in a roster file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CombLoader {
+    name: String,
+    #[serde(rename = "level/hd")]
+    level_hd: u32,
+    class: ClassNames,
+    abilities: Option<Abilities>,
+    hp: HitPoints,
+    ac: i32,
+    /// Attacks per round.
+    attacks: Option<u32>,
+    team: Option<u32>,
+    init: Option<u32>,
+}
+
+/// A roster of combatants.
+pub type CombLoaders = Vec<CombLoader>;
+
+/// Hit points given as a number, or as a "curr/max" string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum HitPoints {
+    Full(i32),
+    Meter(String),
+}
+
+/// One or more class names, or "Monster".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum ClassNames {
+    Single(String),
+    Multi(Vec<String>),
+}
+
+impl CombLoader {
+    /// Return the classes described by the loader at its level,
+    /// if all names are valid.
+    fn classes(&self) -> Option<Classes> {
+        match self.class {
+            ClassNames::Single(ref n) if n.to_lowercase() == "monster" => {
+                Some(Classes::Monster { magical: false, hd_num: 1, hd_den: 1 })
+            },
+            ClassNames::Single(ref n) => n.parse::<Class>().ok()
+                .map(|c| Classes::Single { name: c, lvl: 1 }),
+            ClassNames::Multi(ref v) => v.iter()
+                .map(|n| n.parse::<Class>().ok())
+                .collect::<Option<Vec<_>>>()
+                .map(|c| Classes::Multi { name: c, lvl: 1 }),
+        }.map(|c| c.lvl(self.level_hd))
+    }
+
+    /// Return the hp, where a single value is used as both current and maximum.
+    fn hp(&self) -> Option<Meter<i32>> {
+        match self.hp {
+            HitPoints::Full(n) => Some(Meter::new(n, n)),
+            HitPoints::Meter(ref s) => s.trim().parse::<Meter<i32>>().ok(),
+        }
+    }
+
+    /// Return a builder with every field that could be read.
+    fn builder(&self) -> CombatantBuilder {
+        let mut cb = CombatantBuilder::new(self.name.clone())
+            .hd(self.level_hd)
+            .ac(self.ac);
+        cb.class = self.classes();
+        cb.abilities = self.abilities;
+        cb.hp = self.hp();
+        cb.attacks = self.attacks.map(|n| Meter::new(n, n));
+        cb.team = self.team;
+        cb.init = self.init;
+        cb
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum LoadError {
+    #[fail(display = "Invalid class for {}", _0)]
+    InvalidClass(String),
+    #[fail(display = "Invalid hp for {}", _0)]
+    InvalidHP(String),
+}
+
+impl TryFrom<CombLoader> for CombatantBuilder {
+    type Error = LoadError;
+    /// Convert into a builder, failing if the class or hp cannot be read.
+    fn try_from(cl: CombLoader) -> Result<Self, Self::Error> {
+        let cb = cl.builder();
+        if cb.class.is_none() {
+            return Err(LoadError::InvalidClass(cl.name));
+        }
+        if cb.hp.is_none() {
+            return Err(LoadError::InvalidHP(cl.name));
+        }
+        Ok(cb)
+    }
+}
+
+impl From<CombLoader> for BattleRow {
+    /// Convert into a battle row, building the combatant if possible.
+    fn from(cl: CombLoader) -> Self {
+        let cb = cl.builder();
+        match cb.clone().build() {
+            Ok(c) => BattleRow::Done(c),
+            Err(_) => BattleRow::Building(cb),
+        }
+    }
+}
+
+/// Load a roster of combatants from a file.
+pub fn load_combs(path: &Path) -> Result<Vec<BattleRow>, Error> {
+    let f = File::open(path)?;
+    let reader = BufReader::new(f);
+    let combs : CombLoaders = serde_json::from_reader(reader)?;
+    Ok(combs.into_iter().map(BattleRow::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loader(json: &str) -> CombLoader {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn numeric_hp_fills_meter() {
+        let cl = loader(r#"{"name": "Rat", "level/hd": 1, "class": "Monster", "hp": 3, "ac": 6}"#);
+        assert_eq!(cl.hp(), Some(Meter::new(3, 3)));
+    }
+
+    #[test]
+    fn string_hp_reads_fraction() {
+        let cl = loader(r#"{"name": "Rat", "level/hd": 1, "class": "Monster", "hp": "2/3", "ac": 6}"#);
+        assert_eq!(cl.hp(), Some(Meter::new(2, 3)));
+    }
+
+    #[test]
+    fn example_roster_loads() {
+        let rows = load_combs(Path::new("example.json")).unwrap();
+        assert_eq!(rows.len(), 3);
+        let z = rows[0].done().expect("Zahaedra is fully specified");
+        assert_eq!(z.class, Classes::Multi { name: vec![Class::Ranger, Class::Illusionist], lvl: 1 });
+        assert_eq!(z.hp, Meter::new(13, 13));
+        assert_eq!(z.abilities, Some("15/16/15/16/14/9".parse::<Abilities>().unwrap()));
+        assert_eq!(z.attacks, Meter::new(1, 1));
+        let r = rows[1].done().expect("Rosanna is fully specified");
+        assert_eq!(r.class, Classes::Single { name: Class::Mage, lvl: 1 });
+        assert_eq!(r.hp, Meter::new(5, 5));
+        assert_eq!(r.abilities, Some("13/18/12/11/10/12".parse::<Abilities>().unwrap()));
+        // the rat has no team or initiative yet
+        match rows[2] {
+            BattleRow::Building(ref cb) => assert_eq!(cb.hp, Some(Meter::new(3, 3))),
+            BattleRow::Done(_) => panic!("the rat should still be building"),
+        }
+    }
+
+    #[test]
+    fn missing_team_leaves_row_building() {
+        let json = r#"{"name": "Rat", "level/hd": 1, "class": "Monster", "hp": 3, "ac": 6,
+                       "attacks": 1, "init": 4}"#;
+        assert!(BattleRow::from(loader(json)).done().is_none());
+        let json = r#"{"name": "Rat", "level/hd": 1, "class": "Monster", "hp": 3, "ac": 6,
+                       "attacks": 1, "init": 4, "team": 2}"#;
+        let row = BattleRow::from(loader(json));
+        let rat = row.done().unwrap();
+        assert_eq!((rat.team, rat.init), (2, 4));
+    }
+}
@@ -0,0 +1,48 @@
+//! Record of events during combat.
+
+use combatants::Status;
+use std::fmt;
+
+/// The events of a single round.
+#[derive(Debug, Clone)]
+pub struct RoundLog {
+    pub round: u32,
+    pub entries: Vec<LogEntry>,
+}
+
+/// A single combat event.
+#[derive(Debug, Clone)]
+pub enum LogEntry {
+    Attack { from: String, to: String, damage: i32 },
+    Damage { who: String, amount: i32 },
+    Heal { who: String, amount: i32 },
+    StatusChange { who: String, new_status: Status },
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LogEntry::Attack { ref from, ref to, damage } => {
+                write!(f, "{} hits {} for {}", from, to, damage)
+            },
+            LogEntry::Damage { ref who, amount } => write!(f, "{} takes {}", who, amount),
+            LogEntry::Heal { ref who, amount } => write!(f, "{} heals {}", who, amount),
+            LogEntry::StatusChange { ref who, new_status } => match new_status {
+                Status::Healthy => write!(f, "{} recovers", who),
+                Status::Stunned(x) => write!(f, "{} is stunned ({})", who, x),
+                Status::Unconscious => write!(f, "{} falls unconscious", who),
+                Status::Dead => write!(f, "{} dies", who),
+            },
+        }
+    }
+}
+
+impl fmt::Display for RoundLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Round {}", self.round)?;
+        for entry in &self.entries {
+            write!(f, "\n  {}", entry)?;
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,6 @@
+// failure's Fail derive expands to impls nested in a const, which this lint flags
+#![allow(non_local_definitions)]
+
 extern crate tui;
 extern crate termion;
 extern crate serde;
@@ -6,10 +9,12 @@ extern crate serde_json;
 #[macro_use] extern crate failure;
 extern crate strum;
 #[macro_use] extern crate strum_macros;
+extern crate rand;
 
 use termion::input::TermRead;
 use termion::event;
 use failure::Error;
+use rand::Rng;
 use tui::backend::RawBackend;
 use tui::Terminal;
 
@@ -21,12 +26,18 @@ use std::fs::File;
 use std::path::Path;
 
 use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 mod meters;
 mod combatants;
+mod loader;
+mod dice;
+mod log;
 
 use meters::Meter;
-use combatants::{Combatant, CombatantBuilder, Classes, Abilities, CombatError};
+use log::{RoundLog, LogEntry};
+use combatants::{Combatant, CombatantBuilder, Classes, Abilities, CombatError, SaveCategory, Status};
 
 /// Enum for handling thread-sent events.
 #[derive(Debug, PartialEq)]
@@ -49,14 +60,42 @@ enum Mode {
     //Command(MsgType),
     /// Awaiting a key interpreted as the start of a command
     Normal,
+    /// Displaying the help screen until dismissed
+    Help,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for Mode {
     fn default() -> Self {
         Mode::Normal
     }
 }
 
+/// Controls which layout `draw` renders.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum DisplayMode {
+    /// The combatant table used for tracking combat
+    Table,
+    /// Expanded detail of the selected combatant beside the battle order
+    DMScreen,
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for DisplayMode {
+    fn default() -> Self {
+        DisplayMode::Table
+    }
+}
+
+impl DisplayMode {
+    fn toggle(self) -> Self {
+        match self {
+            DisplayMode::Table => DisplayMode::DMScreen,
+            DisplayMode::DMScreen => DisplayMode::Table,
+        }
+    }
+}
+
 /// Specifies the type of message we want to parse.
 #[derive(EnumString, Display, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum MsgType {
@@ -73,34 +112,70 @@ enum MsgType {
     Name,
     SaveFileName,
     OpenFileName,
+    RosterFileName,
+    Confirm,
+    SaveCategory,
+    Roll,
+    SpellLevel,
+    Notes,
+    EncounterNotes,
+    Count,
+    TempHP,
 }
 
-const _HELP : &'static str = "
-    Flesh Wounds Help:\r
-    F1          display help\r
-    ctrl-c, q   quit\r
-    ctrl-s      save\r
-    ctrl-o      open\r
-    n           new combatant\r
-    I           set combatant initiative\r
+const HELP : &str = "
+    Flesh Wounds Help:
+    F1          display help
+    0-9         repeat the next command a number of times
+    ctrl-c, q   quit
+    ctrl-s      save
+    ctrl-o      open (by path or autosave number)
+    ctrl-l      load roster
+    ctrl-t      break initiative ties
+    ctrl-r      restore all combatants to full HP
+    ctrl-z      undo last change
+    ctrl-a      toggle autosave
+    ctrl-e      export table as Markdown
+    ctrl-d      remove dead combatants
+    alt-t       toggle DM screen
+    Up/Down     scroll combatant detail
+    PgUp/PgDn   scroll DM screen log
+    n           new combatant
+    o           set combatant notes
+    N           set encounter notes
+    R           rename combatant
+    I           set combatant initiative
     T           set combatant team
-    E           set combatant ability scores\r
-    A           set combatant attacks\r
-    C           set combatant class\r
-    H           set combatant HP\r
-    D           set combatant HD\r
-    a           attack self->other\r
-    d           damage self\r
-    h           heal self\r
-    x           advance one round\r
-    y           duplicate combatant\r
-    z           display combatant xp\r
-    Return      select combatant\r
-    j           scroll down\r
-    k           scroll up\r
-    ~           reset combatants to round 1\r
-
-    Press Enter to close this help and return to the program.\r
+    E           set combatant ability scores
+    A           set combatant attacks
+    C           set combatant class
+    H           set combatant HP
+    D           set combatant HD
+    +           level up combatant
+    a           attack self->other
+    r           roll to hit self->other
+    d           damage self (or group)
+    h           heal self (or group)
+    t           grant temporary HP to self
+    m           add/remove combatant from group
+    L           toggle combat log
+    s           roll saving throw
+    S           cast spell
+    v           revive combatant
+    w           toggle combatant spectator
+    x           advance one round
+    y           duplicate combatant
+    Y           duplicate combatant several times
+    Delete      remove combatant
+    z           display combatant xp
+    Return      select combatant
+    j           scroll down
+    k           scroll up
+    alt-j       move combatant down
+    alt-k       move combatant up
+    ~           reset and restore combatants to round 1
+
+    Press Enter or Escape to close this help and return to the program.
 ";
 
 /// Specifies whether or not a row of the battle struct
@@ -125,9 +200,31 @@ impl BattleRow {
             BattleRow::Building(_) => None
         }
     }
+
+    /// Order two rows with equal initiative: higher dexterity first.
+    fn tie_break(&self, other: &BattleRow) -> Ordering {
+        match (self.done(), other.done()) {
+            (Some(a), Some(b)) => b.dex().cmp(&a.dex()),
+            _ => Ordering::Equal,
+        }
+    }
 }
 
 const MAX_COMBATANTS : usize = 32;
+const MAX_HISTORY : usize = 10;
+const MAX_CMD_PREFIX : u32 = 99;
+
+/// Prompts asked in order when adding a combatant.
+const NEW_COMBATANT_PROMPTS : [MsgType; 8] = [
+    MsgType::Name,
+    MsgType::Class,
+    MsgType::HD,
+    MsgType::HP,
+    MsgType::Attacks,
+    MsgType::AC,
+    MsgType::Team,
+    MsgType::Init,
+];
 
 struct Battle {
     size: tui::layout::Rect,
@@ -136,54 +233,165 @@ struct Battle {
     requests: Vec<MsgType>,
     messages: BTreeMap<MsgType, String>,
     sel: Option<usize>,
+    /// Rows chosen for group damage and healing.
+    multi_sel: Vec<usize>,
+    /// Count typed before a command, repeating it that many times.
+    cmd_prefix: Option<u32>,
+    /// Command waiting on prompts, run once they are all answered.
+    pending: Option<event::Key>,
     combatants: Vec<BattleRow>,
+    max_combatants: usize,
     round: u32,
     pos: usize,
+    /// Index of the first combatant shown in the table.
+    scroll: usize,
     autosave: Option<AutosaveSettings>,
+    /// Autosave settings kept while autosave is switched off.
+    paused_autosave: Option<AutosaveSettings>,
+    /// Number of updates processed, used to space out autosaves.
+    action_count: u32,
+    /// Recent autosaves offered when opening a file, newest first.
+    recent_saves: Vec<String>,
+    notice: Option<String>,
+    error: Option<String>,
+    display_mode: DisplayMode,
+    detail_scroll: u16,
+    log_scroll: u16,
+    log: Vec<RoundLog>,
+    show_log: bool,
+    /// Notes on the encounter as a whole, shown on the DM screen.
+    notes: String,
+    /// Snapshots of the round and combatants before recent changes, newest last.
+    history: Vec<String>,
 }
 
 struct AutosaveSettings {
     prefix: String,
     max_saves: u32,
-    save: u32,
+    /// Number of changes between saves.
+    interval: u32,
 }
 
 impl AutosaveSettings {
-    fn get_save_path(&mut self) -> String {
-        self.save = (self.save + 1) % self.max_saves;
-        format!("{}{}.json", self.prefix, self.save)
+    /// Return an unused save path named for the current time,
+    /// numbered to tell apart saves made within the same second.
+    fn get_save_path(&self) -> String {
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let saves = self.read_manifest();
+        let mut seq = 0;
+        loop {
+            let path = format!("{}{}-{}.json", self.prefix, ts, seq);
+            if !saves.contains(&path) && !Path::new(&path).exists() {
+                return path;
+            }
+            seq += 1;
+        }
+    }
+
+    fn manifest_path(&self) -> String {
+        format!("{}manifest.json", self.prefix)
+    }
+
+    /// Return the paths of recent saves, oldest first.
+    fn read_manifest(&self) -> Vec<String> {
+        File::open(self.manifest_path()).ok()
+            .and_then(|f| serde_json::from_reader(BufReader::new(f)).ok())
+            .unwrap_or_default()
+    }
+
+    /// Add a save to the manifest, deleting the oldest saves beyond the limit.
+    fn record_save(&self, path: String) -> Result<(), Error> {
+        let mut listed = self.read_manifest();
+        listed.push(path);
+        // keep only the latest entry for each file, so a save still listed is never deleted
+        let mut saves = Vec::with_capacity(listed.len());
+        for p in listed.into_iter().rev() {
+            if !saves.contains(&p) {
+                saves.push(p);
+            }
+        }
+        saves.reverse();
+        while saves.len() > self.max_saves.max(1) as usize {
+            let old = saves.remove(0);
+            // the file may already have been removed by hand
+            let _ = std::fs::remove_file(old);
+        }
+        let f = File::create(self.manifest_path())?;
+        serde_json::to_writer_pretty(BufWriter::new(f), &saves)?;
+        Ok(())
     }
 }
 
 impl Default for AutosaveSettings {
     /// Create default autosave.
     fn default() -> Self {
-        AutosaveSettings { prefix: ".auto".into(), max_saves: 5, save: 0 }
+        AutosaveSettings { prefix: ".auto".into(), max_saves: 5, interval: 5 }
     }
 }
 
-// #[derive(Debug, Fail)]
-// enum BattleError {
-//     #[fail(display = "No input received")]
-//     NoInput,
-// }
+#[derive(Debug, Fail)]
+enum BattleError {
+    // #[fail(display = "No input received")]
+    // NoInput,
+    #[fail(display = "Cannot have more than {} combatants", _0)]
+    TooManyCombatants(usize),
+    #[fail(display = "Save file has no version and cannot be loaded")]
+    MissingSaveVersion,
+    #[fail(display = "Save file version {} is not supported (expected 1)", _0)]
+    BadSaveVersion(u64),
+}
+
+const SAVE_VERSION : u64 = 1;
+
+/// The contents of a save file.
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u64,
+    round: u32,
+    pos: usize,
+    sel: Option<usize>,
+    combatants: Vec<BattleRow>,
+    #[serde(default)]
+    notes: String,
+}
 
 /// Set the field of a row.
 macro_rules! set_row {
     ($field:ident: $type:ty) => {
+        #[allow(dead_code)]
         fn $field(&mut self, $field: $type) {
             if self.pos < self.combatants.len() {
                 match self.combatants[self.pos] {
                     BattleRow::Building(ref mut cb) => cb.$field = Some($field),
                     BattleRow::Done(ref mut c) => c.$field = $field,
                 }
+                let pos = self.pos;
+                self.try_build(pos);
             }
         }
     }
 }
 
+impl Default for Battle {
+    fn default() -> Self {
+        Battle::new()
+    }
+}
+
 impl Battle {
     fn new() -> Self {
+        Battle::with_max_combatants(MAX_COMBATANTS)
+    }
+
+    /// Create a battle that will be drawn into an area of the given size.
+    fn with_size(size: tui::layout::Rect) -> Self {
+        Battle { size, ..Battle::new() }
+    }
+
+    /// Create a battle holding at most the given number of combatants.
+    fn with_max_combatants(max_combatants: usize) -> Self {
         Battle {
             size: tui::layout::Rect::default(),
             mode: Mode::default(),
@@ -191,10 +399,27 @@ impl Battle {
             requests: vec![],
             messages: BTreeMap::new(),
             sel: None,
-            combatants: Vec::with_capacity(MAX_COMBATANTS),
+            multi_sel: vec![],
+            cmd_prefix: None,
+            pending: None,
+            combatants: Vec::with_capacity(max_combatants),
+            max_combatants,
             round: 1,
             pos: 0,
+            scroll: 0,
             autosave: Some(AutosaveSettings::default()),
+            paused_autosave: None,
+            action_count: 0,
+            recent_saves: vec![],
+            notice: None,
+            error: None,
+            display_mode: DisplayMode::default(),
+            detail_scroll: 0,
+            log_scroll: 0,
+            log: vec![],
+            show_log: false,
+            notes: String::new(),
+            history: vec![],
         }
     }
 
@@ -202,28 +427,123 @@ impl Battle {
     fn load_combat<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
         let f = File::open(path)?;
         let reader = BufReader::new(f);
-        let (round, combatants) : (u32, Vec<BattleRow>) = serde_json::from_reader(reader)?;
-        self.round = round;
-        self.combatants = combatants;
+        let value : serde_json::Value = serde_json::from_reader(reader)?;
+        // check the version before the layout so old files give a clear error
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(SAVE_VERSION) => (),
+            Some(v) => return Err(BattleError::BadSaveVersion(v).into()),
+            None => return Err(BattleError::MissingSaveVersion.into()),
+        }
+        let save : SaveFile = serde_json::from_value(value)?;
+        if save.combatants.len() > self.max_combatants {
+            return Err(BattleError::TooManyCombatants(self.max_combatants).into());
+        }
+        self.round = save.round;
+        self.combatants = save.combatants;
+        self.pos = save.pos.min(self.combatants.len().saturating_sub(1));
+        self.sel = save.sel.filter(|&i| i < self.combatants.len());
+        self.notes = save.notes;
+        self.multi_sel.clear();
         Ok(())
     }
 
+    /// Add the combatants from a roster file to the battle,
+    /// returning the number of rows added.
+    fn import_roster<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, Error> {
+        let rows = loader::load_combs(path.as_ref())?;
+        self.check_room(rows.len())?;
+        let n = rows.len();
+        self.combatants.extend(rows);
+        self.sort();
+        Ok(n)
+    }
+
     fn save_combat<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
         let f = File::create(path)?;
         let writer = BufWriter::new(f);
-        let () = serde_json::to_writer_pretty(writer, &(self.round, &self.combatants))?;
+        let save = SaveFile {
+            version: SAVE_VERSION,
+            round: self.round,
+            pos: self.pos,
+            sel: self.sel,
+            combatants: self.combatants.clone(),
+            notes: self.notes.clone(),
+        };
+        let () = serde_json::to_writer_pretty(writer, &save)?;
         Ok(())
     }
 
-    /// Autosave game state.
+    /// Return the combatants as a Markdown table.
+    fn export_markdown(&self) -> String {
+        fn opt<T: ToString>(v: Option<T>) -> String {
+            v.map(|t| t.to_string()).unwrap_or_else(|| "-".into())
+        }
+        let mut lines = vec![
+            format!("## Round {}", self.round),
+            String::new(),
+            "| Name | Team | Init | HP | AC | THAC0 | Status | Notes |".to_string(),
+            "|---|---|---|---|---|---|---|---|".to_string(),
+        ];
+        for row in &self.combatants {
+            let cells = match row {
+                BattleRow::Done(c) => vec![c.name.clone(), c.team.to_string(), c.init.to_string(),
+                    c.hp.to_string(), c.ac.to_string(), c.thac0.to_string(),
+                    c.status.describe(), c.notes.clone()],
+                BattleRow::Building(cb) => vec![cb.name.clone(), opt(cb.team), opt(cb.init),
+                    opt(cb.hp), opt(cb.ac), "-".into(), "-".into(), cb.notes.clone()],
+            };
+            // keep each cell on one line and out of the table's way
+            let cells = cells.iter()
+                .map(|c| c.replace('|', "\\|").replace('\n', " "))
+                .collect::<Vec<_>>();
+            lines.push(format!("| {} |", cells.join(" | ")));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Write the combatants as Markdown to a file named for the round,
+    /// returning the file name.
+    fn export(&self) -> Result<String, Error> {
+        let path = format!("battle_round_{}.md", self.round);
+        std::fs::write(&path, self.export_markdown())?;
+        Ok(path)
+    }
+
+    /// Autosave game state once every interval of changes.
     fn autosave(&mut self) -> Result<(), Error> {
-        let x = if let Some(ref mut a) = self.autosave {
+        self.action_count = self.action_count.wrapping_add(1);
+        let x = if let Some(ref a) = self.autosave {
+            if !self.action_count.is_multiple_of(a.interval.max(1)) {
+                return Ok(())
+            }
             a.get_save_path()
         } else {
             // jump out
             return Ok(())
         };
-        self.save_combat(x)
+        self.save_combat(&x)?;
+        match self.autosave {
+            Some(ref a) => a.record_save(x),
+            None => Ok(()),
+        }
+    }
+
+    /// Return the paths of recent autosaves, newest first.
+    fn recent_autosaves(&self) -> Vec<String> {
+        self.autosave.as_ref()
+            .or(self.paused_autosave.as_ref())
+            .map(|a| a.read_manifest().into_iter().rev().collect())
+            .unwrap_or_default()
+    }
+
+    /// Switch autosave off, or back on with the previous settings.
+    fn toggle_autosave(&mut self) {
+        match self.autosave.take() {
+            Some(a) => self.paused_autosave = Some(a),
+            None => {
+                self.autosave = Some(self.paused_autosave.take().unwrap_or_default());
+            },
+        }
     }
 
     // fn draw(&mut self) {
@@ -256,20 +576,80 @@ impl Battle {
     // }
 
     /// Update the battle based on the given event.
+    /// Errors are kept for display in the prompt and the pending command is cancelled.
     fn update(&mut self, evt: Event) -> Result<(), Error> {
-        macro_rules! get_or_req {
-            ($msg:expr, $process:expr) => {
-                {
-                    if let Some(p) = self.messages.get(&$msg) {
-                        //self.mode = Mode::Command(msg);
-                        $process(p)
-                    } else {
-                        self.mode = Mode::Insert($msg);
-                        return Ok(());
-                    }
+        let before = if self.may_change_state(&evt) {
+            Some(self.snapshot()?)
+        } else {
+            None
+        };
+        if let Err(e) = self.process(evt) {
+            self.error = Some(e.to_string());
+            self.input.clear();
+            self.requests.clear();
+            self.messages.clear();
+            self.cmd_prefix = None;
+            self.pending = None;
+            self.mode = Mode::Normal;
+        }
+        if let Some(before) = before {
+            if before != self.snapshot()? {
+                self.history.push(before);
+                if self.history.len() > MAX_HISTORY {
+                    self.history.remove(0);
                 }
-            };
+                return self.autosave();
+            }
+        }
+        Ok(())
+    }
+
+    /// Return true if the event may run a command that changes the round,
+    /// combatants or log, and so needs an undo snapshot.
+    fn may_change_state(&self, evt: &Event) -> bool {
+        use termion::event::Key::*;
+        let Event::Input(key) = *evt;
+        match self.mode {
+            Mode::Help => false,
+            // commands run in normal mode, or on answering their last prompt
+            Mode::Insert(_) => key == Char('\n'),
+            Mode::Normal => match key {
+                Ctrl('z') | Ctrl('s') | Ctrl('e') | Ctrl('a') | F(1) => false,
+                Char('j') | Char('k') | Char('m') | Char('\n') | Char('L') | Char('z') => false,
+                Alt('t') | Up | Down | PageUp | PageDown => false,
+                // digits only build up a count
+                Char(c) => !c.is_ascii_digit(),
+                _ => true,
+            },
         }
+    }
+
+    /// Serialize the round, the number of log entries and the combatants.
+    fn snapshot(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string(&(self.round, self.log_len(), &self.combatants))?)
+    }
+
+    /// Revert the round, log and combatants to before the last change.
+    fn undo(&mut self) -> Result<(), Error> {
+        let last = match self.history.pop() {
+            Some(s) => s,
+            None => {
+                self.notice = Some("Nothing to undo".into());
+                return Ok(());
+            },
+        };
+        let (round, log_len, combatants) : (u32, usize, Vec<BattleRow>) = serde_json::from_str(&last)?;
+        self.round = round;
+        self.truncate_log(log_len);
+        self.combatants = combatants;
+        self.pos = self.pos.min(self.combatants.len().saturating_sub(1));
+        self.sel = self.sel.filter(|&i| i < self.combatants.len());
+        self.multi_sel.clear();
+        Ok(())
+    }
+
+    /// Process the given event.
+    fn process(&mut self, evt: Event) -> Result<(), Error> {
         use termion::event::Key::*;
         match self.mode {
             Mode::Insert(msg) => {
@@ -281,6 +661,10 @@ impl Battle {
                                 self.mode = Mode::Insert(req);
                             } else {
                                 self.mode = Mode::Normal;
+                                // all prompts answered, so run the command that asked
+                                if let Some(key) = self.pending.take() {
+                                    return self.process(Event::Input(key));
+                                }
                             }
                         },
                         Char(c) => {
@@ -293,12 +677,22 @@ impl Battle {
                             // erase input and cancel command
                             self.input.clear();
                             self.requests.clear();
+                            self.messages.clear();
+                            self.cmd_prefix = None;
+                            self.pending = None;
                             self.mode = Mode::Normal;
                         },
                         _ => (),
                     },
                 }
             },
+            Mode::Help => {
+                match evt {
+                    Event::Input(Char('\n')) | Event::Input(Esc) => self.mode = Mode::Normal,
+                    _ => (),
+                }
+                return Ok(());
+            },
             // Mode::Char => {
             //     if let Some(msg) = self.requests.pop() {
             //         match evt {
@@ -319,176 +713,579 @@ impl Battle {
             //     }
             // },
             _ => {
-                match evt {
-                    Event::Input(input) => match input {
-                        Ctrl('s') => {
-                            get_or_req!(MsgType::SaveFileName,
-                                |save| self.save_combat(save))?;
-                        },
-                        Ctrl('o') => {
-                            let open = get_or_req!(MsgType::OpenFileName,
-                                |p : &String| p.clone());
-                            self.load_combat(open)?;
-                        },
-                        Char('j') => self.down(),
-                        Char('k') => self.up(),
-                        Char('x') => self.advance(),
-                        Char('n') => {
-                            let name = get_or_req!(MsgType::Name,
-                                |p: &String| p.clone());
-                            let _class = get_or_req!(MsgType::Class,
-                                |p: &String| p.parse::<Classes>())?;
-                            let _ac = get_or_req!(MsgType::AC,
-                                |p: &String| p.parse::<i32>())?;
-                            self.add_combatant(name);
-                        },
-                        Char('i') => {
-                            let team = get_or_req!(MsgType::Team,
-                                |p: &String| p.parse::<u32>())?;
-                            self.team(team);
-                            let init = get_or_req!(MsgType::Init,
-                                |p: &String| p.parse::<u32>())?;
-                            self.init(init);
-                        },
-                        Char('E') => {
-                            let abils = get_or_req!(MsgType::Abilities,
-                                |p: &String| p.parse::<Abilities>()).ok();
-                            self.add_abilities(abils);
-                        },
-                        Char('\n') => {
-                            self.sel = match self.sel {
-                                Some(i) if i == self.pos => None,
-                                _ => Some(self.pos),
-                            };
-                        },
-                        Char('A') => {
-                            let atts = get_or_req!(MsgType::Attacks,
-                                |p: &String| p.parse::<Meter<u32>>())?;
-                            self.attacks(atts);
-                        },
-                        Char('a') => {
-                            // make sure from has enough attacks
-                            let dam = get_or_req!(MsgType::Damage,
-                                |p: &String| p.parse::<i32>())?;
-                            self.attack(dam)?;
-                        },
-                        Char('C') => {
-                            let class = get_or_req!(MsgType::Class,
-                                |p: &String| p.parse::<Classes>())?;
-                            self.class(class);
-                        },
-                        Char('D') => {
-                            let hd = get_or_req!(MsgType::HD,
-                                |p: &String| p.parse::<u32>())?;
-                            self.hd(hd);
-                        },
-                        Char('d') => {
-                            let dam = get_or_req!(MsgType::Damage,
-                                |p: &String| p.parse::<i32>())?;
-                            self.damage(dam)?;
-                        },
-                        Char('H') => {
-                            let hp = get_or_req!(MsgType::HP,
-                                |p: &String| p.parse::<Meter<i32>>())?;
-                            self.hp(hp);
-                        },
-                        Char('h') => {
-                            let heal = get_or_req!(MsgType::Healing,
-                                |p: &String| p.parse::<i32>())?;
-                            self.heal(heal)?;
-                        },
-                        Char('y') => {
-                            let s = get_or_req!(MsgType::Name,
-                                |p: &String| p.clone());
-                            let name = if s.len() == 0 {
-                                None
-                            } else {
-                                Some(s)
-                            };
-                            self.copy_combatant(name);
-                        },
-                        Char('z') => {
-                            self.get_xp().unwrap();
-                        },
-                        Char('~') => {
-                            // Reset all combatants.
-                            for comb in &mut self.combatants {
-                                if let BattleRow::Done(c) = comb {
-                                    c.reset();
-                                }
+                self.notice = None;
+                self.error = None;
+                let Event::Input(input) = evt;
+                // digits build up a count to repeat the next command
+                if let Char(c) = input {
+                    if let Some(d) = c.to_digit(10) {
+                        if d > 0 || self.cmd_prefix.is_some() {
+                            // digits past the largest count are dropped
+                            let n = self.cmd_prefix.unwrap_or(0) * 10 + d;
+                            if n <= MAX_CMD_PREFIX {
+                                self.cmd_prefix = Some(n);
                             }
-                        },
-                        F(1) => {
-                            // display help
-                        },
-                        _ => (),
+                            return Ok(());
+                        }
+                    }
+                }
+                for _ in 0..self.cmd_prefix.unwrap_or(1) {
+                    self.command(input)?;
+                    if self.mode != Mode::Normal {
+                        // waiting on input, so keep the count for when it arrives
+                        return Ok(());
+                    }
+                }
+                self.cmd_prefix = None;
+                self.messages.clear();
+            },
+        }
+        Ok(())
+    }
+
+    /// Run the command for the given key in normal mode.
+    fn command(&mut self, input: event::Key) -> Result<(), Error> {
+        macro_rules! get_or_req {
+            ($msg:expr, $process:expr) => {
+                {
+                    if let Some(p) = self.messages.get(&$msg) {
+                        //self.mode = Mode::Command(msg);
+                        $process(p)
+                    } else {
+                        self.mode = Mode::Insert($msg);
+                        self.pending = Some(input);
+                        return Ok(());
+                    }
+                }
+            };
+        }
+        use termion::event::Key::*;
+        match input {
+            Ctrl('s') => {
+                get_or_req!(MsgType::SaveFileName,
+                    |save| self.save_combat(save))?;
+            },
+            Ctrl('o') => {
+                if !self.messages.contains_key(&MsgType::OpenFileName) {
+                    // offer recent autosaves by number
+                    self.recent_saves = self.recent_autosaves();
+                }
+                let open = get_or_req!(MsgType::OpenFileName,
+                    |p : &String| p.clone());
+                let open = match open.trim().parse::<usize>() {
+                    Ok(n) if n >= 1 && n <= self.recent_saves.len() => {
+                        self.recent_saves[n - 1].clone()
                     },
+                    _ => open,
+                };
+                self.load_combat(open)?;
+            },
+            Ctrl('l') => {
+                let roster = get_or_req!(MsgType::RosterFileName,
+                    |p : &String| p.clone());
+                let n = self.import_roster(roster)?;
+                self.notice = Some(format!("Imported {} combatants", n));
+            },
+            Ctrl('r') => self.restore_all_hp(),
+            Ctrl('z') => self.undo()?,
+            Ctrl('a') => self.toggle_autosave(),
+            Ctrl('e') => {
+                let path = self.export()?;
+                self.notice = Some(format!("Exported to {}", path));
+            },
+            Ctrl('d') => {
+                let n = self.clear_dead();
+                self.notice = Some(format!("Removed {} dead combatants", n));
+            },
+            Char('j') => self.down(),
+            Char('k') => self.up(),
+            Alt('j') => {
+                let (pos, last) = (self.pos, self.combatants.len().saturating_sub(1));
+                self.swap_positions(pos, (pos + 1).min(last));
+            },
+            Alt('k') => {
+                let pos = self.pos;
+                self.swap_positions(pos, pos.saturating_sub(1));
+            },
+            Char('x') => self.advance(),
+            Char('w') => self.toggle_spectator(),
+            Char('m') => self.toggle_multi_sel(),
+            Alt('t') => self.display_mode_switch(),
+            Up => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+            Down => self.detail_scroll = self.detail_scroll.saturating_add(1),
+            PageUp => self.log_scroll = self.log_scroll.saturating_sub(1),
+            PageDown => self.log_scroll = self.log_scroll.saturating_add(1),
+            Ctrl('t') => {
+                self.break_ties_by_dex_roll(&mut rand::thread_rng());
+                self.sort();
+            },
+            Char('n') => {
+                if self.messages.is_empty() {
+                    // ask for everything needed to build the combatant in one go
+                    self.requests = NEW_COMBATANT_PROMPTS[1..].iter().rev().cloned().collect();
+                }
+                let name = get_or_req!(MsgType::Name,
+                    |p: &String| p.clone());
+                let class = get_or_req!(MsgType::Class,
+                    |p: &String| p.parse::<Classes>())?;
+                let hd = get_or_req!(MsgType::HD,
+                    |p: &String| p.parse::<u32>())?;
+                let hp = get_or_req!(MsgType::HP,
+                    |p: &String| dice::parse_dice_meter(p))?;
+                let atts = get_or_req!(MsgType::Attacks,
+                    |p: &String| p.parse::<Meter<u32>>())?;
+                let ac = get_or_req!(MsgType::AC,
+                    |p: &String| p.parse::<i32>())?;
+                let team = get_or_req!(MsgType::Team,
+                    |p: &String| p.parse::<u32>())?;
+                let init = get_or_req!(MsgType::Init,
+                    |p: &String| p.parse::<u32>())?;
+                self.add_combatant(CombatantBuilder::new(name)
+                    .class(class)
+                    .hd(hd)
+                    .hp(hp)
+                    .attacks(atts)
+                    .ac(ac)
+                    .team(team)
+                    .init(init))?;
+            },
+            Char('I') => {
+                let init = get_or_req!(MsgType::Init,
+                    |p: &String| p.parse::<u32>())?;
+                self.init(init);
+            },
+            Char('T') => {
+                let team = get_or_req!(MsgType::Team,
+                    |p: &String| p.parse::<u32>())?;
+                self.team(team);
+            },
+            Char('E') => {
+                let abils = get_or_req!(MsgType::Abilities,
+                    |p: &String| p.parse::<Abilities>()).ok();
+                self.add_abilities(abils);
+            },
+            Char('\n') => {
+                self.sel = match self.sel {
+                    Some(i) if i == self.pos => None,
+                    _ => Some(self.pos),
+                };
+            },
+            Char('A') => {
+                let atts = get_or_req!(MsgType::Attacks,
+                    |p: &String| p.parse::<Meter<u32>>())?;
+                self.attacks(atts);
+            },
+            Char('a') => {
+                // make sure from has enough attacks
+                let dam = get_or_req!(MsgType::Damage,
+                    |p: &String| dice::parse_amount(p))?;
+                self.attack(dam)?;
+            },
+            Char('C') => {
+                let class = get_or_req!(MsgType::Class,
+                    |p: &String| p.parse::<Classes>())?;
+                self.class(class);
+            },
+            Char('D') => {
+                let hd = get_or_req!(MsgType::HD,
+                    |p: &String| p.parse::<u32>())?;
+                self.hd(hd);
+            },
+            Char('d') => {
+                let dam = get_or_req!(MsgType::Damage,
+                    |p: &String| dice::parse_amount(p))?;
+                self.damage(dam)?;
+            },
+            Char('H') => {
+                let hp = get_or_req!(MsgType::HP,
+                    |p: &String| dice::parse_dice_meter(p))?;
+                self.hp(hp);
+            },
+            Char('h') => {
+                let heal = get_or_req!(MsgType::Healing,
+                    |p: &String| dice::parse_amount(p))?;
+                self.heal(heal)?;
+            },
+            Char('y') => {
+                let s = get_or_req!(MsgType::Name,
+                    |p: &String| p.clone());
+                let name = if s.is_empty() {
+                    None
+                } else {
+                    Some(s)
+                };
+                self.copy_combatant(name)?;
+            },
+            Delete => {
+                let confirm = get_or_req!(MsgType::Confirm,
+                    |p: &String| p.to_lowercase().starts_with('y'));
+                if confirm {
+                    let pos = self.pos;
+                    self.remove_combatant(pos);
                 }
+            },
+            Char('s') => {
+                let cat = get_or_req!(MsgType::SaveCategory,
+                    |p: &String| p.parse::<SaveCategory>())?;
+                let roll = get_or_req!(MsgType::Roll,
+                    |p: &String| dice::parse_dice(p))?;
+                self.save(cat, roll)?;
+            },
+            Char('r') => {
+                let roll = get_or_req!(MsgType::Roll,
+                    |p: &String| p.parse::<u32>())?;
+                self.resolve_hit(roll)?;
+            },
+            Char('S') => {
+                let lvl = get_or_req!(MsgType::SpellLevel,
+                    |p: &String| p.parse::<usize>())?;
+                self.cast(lvl)?;
+            },
+            Char('t') => {
+                let amount = get_or_req!(MsgType::TempHP,
+                    |p: &String| dice::parse_amount(p))?;
+                self.grant_temp_hp(amount)?;
+            },
+            Char('v') => {
+                let hp = get_or_req!(MsgType::HP,
+                    |p: &String| dice::parse_dice(p))?;
+                self.revive(hp)?;
+            },
+            Char('+') => {
+                if let Some(c) = self.combatants.get_mut(self.pos).and_then(|r| r.done_mut()) {
+                    c.level_up();
+                }
+            },
+            Char('R') => {
+                let name = get_or_req!(MsgType::Name,
+                    |p: &String| p.clone());
+                self.rename(name);
+            },
+            Char('o') => {
+                let notes = get_or_req!(MsgType::Notes,
+                    |p: &String| p.clone());
+                self.set_notes(notes);
+            },
+            Char('N') => {
+                self.notes = get_or_req!(MsgType::EncounterNotes,
+                    |p: &String| p.clone());
+            },
+            Char('L') => self.show_log = !self.show_log,
+            Char('Y') => {
+                let count = get_or_req!(MsgType::Count,
+                    |p: &String| p.parse::<u32>())?;
+                let template = get_or_req!(MsgType::Name,
+                    |p: &String| p.clone());
+                let source = self.sel.unwrap_or(self.pos);
+                self.copy_group(source, count, &template)?;
+            },
+            Char('z') => {
+                self.get_xp().unwrap();
+            },
+            Char('~') => {
+                // Reset all combatants.
+                for c in self.iter_done_mut() {
+                    c.full_reset();
+                }
+            },
+            F(1) => {
                 self.messages.clear();
-                self.mode = Mode::Normal;
+                self.cmd_prefix = None;
+                self.mode = Mode::Help;
             },
+            _ => (),
         }
-        self.autosave()?;
         Ok(())
     }
 
+    /// Iterate over the built combatants.
+    fn iter_done(&self) -> impl Iterator<Item = &Combatant> {
+        self.combatants.iter().filter_map(|row| row.done())
+    }
+
+    /// Iterate mutably over the built combatants.
+    fn iter_done_mut(&mut self) -> impl Iterator<Item = &mut Combatant> {
+        self.combatants.iter_mut().filter_map(|row| row.done_mut())
+    }
+
     /// Advance to the next round.
     fn advance(&mut self) {
         self.round += 1;
         self.sort();
-        for comb in &mut self.combatants {
-            if let BattleRow::Done(c) = comb {
-                c.update();
+        for c in self.iter_done_mut() {
+            c.update();
+        }
+        if self.has_initiative_ties() {
+            let names = self.initiative_tie_groups().values()
+                .filter(|g| g.len() > 1)
+                .map(|g| g.iter()
+                     .filter_map(|&i| self.combatants[i].done())
+                     .map(|c| c.name.clone())
+                     .collect::<Vec<_>>().join("/"))
+                .collect::<Vec<_>>().join(", ");
+            self.notice = Some(format!("Initiative tied: {} (ctrl-t to break)", names));
+        }
+    }
+
+    /// Group the indices of living combatants by their current initiative.
+    fn initiative_tie_groups(&self) -> BTreeMap<u32, Vec<usize>> {
+        let mut groups = BTreeMap::new();
+        for (i, row) in self.combatants.iter().enumerate() {
+            if let BattleRow::Done(c) = row {
+                if c.get_init() > 0 {
+                    groups.entry(c.get_init()).or_insert_with(Vec::new).push(i);
+                }
+            }
+        }
+        groups
+    }
+
+    /// Return true if two or more combatants share the same initiative.
+    fn has_initiative_ties(&self) -> bool {
+        self.initiative_tie_groups().values().any(|g| g.len() > 1)
+    }
+
+    /// Perturb the base initiative of each tied combatant by a d3 roll.
+    fn break_ties_by_dex_roll(&mut self, rng: &mut impl Rng) {
+        for group in self.initiative_tie_groups().values().filter(|g| g.len() > 1) {
+            for &i in group {
+                if let BattleRow::Done(ref mut c) = self.combatants[i] {
+                    c.init += rng.gen_range(1, 4);
+                }
             }
         }
     }
 
     /// Sort the combatants' ordering based on initiative and status.
+    /// Ties are resolved by dexterity, then by the existing order.
     /// Remove any combatants with Status::Dead from the table.
     fn sort(&mut self) {
+        self.clear_dead();
         let mut initiatives = self.combatants.clone().into_iter()
-            .map(|row| (match row {
+            .enumerate()
+            .map(|(i, row)| (match row {
                  BattleRow::Done(ref c) => Some(c.get_init()),
                  BattleRow::Building(_) => None,
-            }, row))
-            // filter out dead, but keep uninitialized
-            .filter(|&(i, _)| match i { Some(n) => n > 0, None => true })
+            }, i, row))
             .collect::<Vec<_>>();
         // sort with fastest at the top (None elements go to bottom!)
-        initiatives.sort_by(|a, b| b.0.cmp(&a.0));
+        initiatives.sort_by(|a, b| b.0.cmp(&a.0)
+            .then_with(|| a.2.tie_break(&b.2))
+            .then_with(|| a.1.cmp(&b.1)));
         self.combatants = initiatives.into_iter()
-            .map(|(_, c)| c)
+            .map(|(_, _, c)| c)
             .collect::<Vec<_>>();
         // reset pos to 0 to avoid errors
         self.pos = 0;
+        self.scroll = 0;
+        self.multi_sel.clear();
+    }
+
+    /// Switch between the combatant table and the DM screen.
+    fn display_mode_switch(&mut self) {
+        self.display_mode = self.display_mode.toggle();
+        self.detail_scroll = 0;
+        self.log_scroll = 0;
+    }
+
+    /// Restore every combatant to full hp and attacks, keeping round statistics.
+    fn restore_all_hp(&mut self) {
+        for c in self.iter_done_mut() {
+            c.restore();
+        }
+    }
+
+    /// Toggle whether the combatant under the cursor is a spectator.
+    fn toggle_spectator(&mut self) {
+        if self.pos < self.combatants.len() {
+            if let BattleRow::Done(ref mut c) = self.combatants[self.pos] {
+                c.spectator = !c.spectator;
+            }
+            self.sort();
+        }
+    }
+
+    /// Return the number of spectators in the battle.
+    fn spectator_count(&self) -> usize {
+        self.iter_done()
+            .filter(|c| c.spectator)
+            .count()
+    }
+
+    /// Check that the given number of combatants can be added to the battle.
+    fn check_room(&self, n: usize) -> Result<(), BattleError> {
+        if self.combatants.len() + n > self.max_combatants {
+            Err(BattleError::TooManyCombatants(self.max_combatants))
+        } else {
+            Ok(())
+        }
     }
 
     /// Add a combatant to the battle.
-    fn add_combatant(&mut self, name: String) {
-        let c = CombatantBuilder::new(name);
-        self.combatants.push(BattleRow::Building(c));
+    fn add_combatant(&mut self, cb: CombatantBuilder) -> Result<(), BattleError> {
+        self.check_room(1)?;
+        self.combatants.push(BattleRow::Building(cb));
+        let idx = self.combatants.len() - 1;
+        self.try_build(idx);
         self.sort();
+        Ok(())
     }
 
     fn add_abilities(&mut self, abils: Option<Abilities>) {
         if self.pos < self.combatants.len() {
-            if let BattleRow::Done(ref mut c) = self.combatants[self.pos] {
-                c.abilities = abils;
+            match self.combatants[self.pos] {
+                BattleRow::Building(ref mut cb) => cb.abilities = abils,
+                BattleRow::Done(ref mut c) => c.abilities = abils,
             }
+            let pos = self.pos;
+            self.try_build(pos);
+        }
+    }
+
+    /// Try to finish building the combatant at the given index,
+    /// noting the missing field if it cannot be built yet.
+    fn try_build(&mut self, idx: usize) {
+        let built = match self.combatants[idx] {
+            BattleRow::Building(ref cb) => cb.clone().build(),
+            BattleRow::Done(_) => return,
+        };
+        match built {
+            Ok(c) => self.combatants[idx] = BattleRow::Done(c),
+            Err(e) => self.notice = Some(e.to_string()),
         }
     }
 
     set_row!(class: Classes);
     set_row!(hd: u32);
-    set_row!(hp: Meter<i32>);
     set_row!(attacks: Meter<u32>);
     set_row!(ac: i32);
     set_row!(init: u32);
     set_row!(team: u32);
 
+    /// Remove the combatant at the given index, keeping the cursor and selection valid.
+    fn remove_combatant(&mut self, idx: usize) {
+        if idx >= self.combatants.len() {
+            return;
+        }
+        self.combatants.remove(idx);
+        if self.pos > idx || (self.pos == idx && self.pos == self.combatants.len()) {
+            self.pos = self.pos.saturating_sub(1);
+        }
+        self.sel = match self.sel {
+            Some(i) if i == idx => None,
+            Some(i) if i > idx => Some(i - 1),
+            s => s,
+        };
+        self.multi_sel = self.multi_sel.iter()
+            .filter(|&&i| i != idx)
+            .map(|&i| if i > idx { i - 1 } else { i })
+            .collect();
+    }
+
+    /// Remove all dead combatants, returning how many were removed.
+    fn clear_dead(&mut self) -> usize {
+        let dead = self.combatants.iter()
+            .enumerate()
+            .filter(|(_, row)| row.done().map(|c| c.status == Status::Dead).unwrap_or(false))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        // remove from the back so the remaining indices stay valid
+        for &i in dead.iter().rev() {
+            self.remove_combatant(i);
+        }
+        dead.len()
+    }
+
+    /// Swap two combatants, keeping the cursor and selection on the same combatants.
+    fn swap_positions(&mut self, a: usize, b: usize) {
+        if a >= self.combatants.len() || b >= self.combatants.len() {
+            return;
+        }
+        self.combatants.swap(a, b);
+        let swapped = |i: usize| if i == a { b } else if i == b { a } else { i };
+        self.pos = swapped(self.pos);
+        self.sel = self.sel.map(swapped);
+        self.multi_sel = self.multi_sel.iter().map(|&i| swapped(i)).collect();
+    }
+
+    /// Add or remove the combatant under the cursor from the group selection.
+    fn toggle_multi_sel(&mut self) {
+        if self.pos >= self.combatants.len() {
+            return;
+        }
+        match self.multi_sel.iter().position(|&i| i == self.pos) {
+            Some(i) => {
+                self.multi_sel.remove(i);
+            },
+            None => self.multi_sel.push(self.pos),
+        }
+    }
+
+    /// Return the group selection, or the selected combatant if there is none.
+    fn targets(&self) -> Vec<usize> {
+        if self.multi_sel.is_empty() {
+            self.sel.into_iter().collect()
+        } else {
+            self.multi_sel.clone()
+        }
+    }
+
+    /// Return the targets, failing if any of them is still being built
+    /// so that a group is changed either entirely or not at all.
+    fn built_targets(&self) -> Result<Vec<usize>, CombatError> {
+        let targets = self.targets();
+        if targets.iter().any(|&i| self.combatants[i].done().is_none()) {
+            return Err(CombatError::NotBuilt);
+        }
+        Ok(targets)
+    }
+
+    /// Add `count` copies of the given combatant, named from the template
+    /// followed by a number, with their statistics reset.
+    fn copy_group(&mut self, source_idx: usize, count: u32, name_template: &str) -> Result<(), BattleError> {
+        if source_idx >= self.combatants.len() {
+            return Ok(());
+        }
+        self.check_room(count as usize)?;
+        for i in 1..=count {
+            let mut new = self.combatants[source_idx].clone();
+            let name = format!("{} {}", name_template, i);
+            match new {
+                BattleRow::Done(ref mut c) => {
+                    c.rename(name);
+                    c.reset_stats();
+                },
+                BattleRow::Building(ref mut cb) => cb.name = name,
+            }
+            self.combatants.push(new);
+        }
+        Ok(())
+    }
+
+    /// Set the notes of the combatant under the cursor.
+    fn set_notes(&mut self, notes: String) {
+        if self.pos < self.combatants.len() {
+            match self.combatants[self.pos] {
+                BattleRow::Building(ref mut cb) => cb.notes = notes,
+                BattleRow::Done(ref mut c) => c.notes = notes,
+            }
+        }
+    }
+
+    /// Rename the combatant under the cursor.
+    fn rename(&mut self, name: String) {
+        if self.pos < self.combatants.len() {
+            match self.combatants[self.pos] {
+                BattleRow::Building(ref mut cb) => cb.name = name,
+                BattleRow::Done(ref mut c) => c.rename(name),
+            }
+        }
+    }
+
     /// Duplicate the combatant underneath the cursor, renaming if given a new name.
-    fn copy_combatant<S: Into<String>>(&mut self, name: Option<S>) {
+    fn copy_combatant<S: Into<String>>(&mut self, name: Option<S>) -> Result<(), BattleError> {
         if let Some(f) = self.sel {
+            self.check_room(1)?;
             let mut new = self.combatants[f].clone();
             if let Some(name) = name {
                 match new {
@@ -498,33 +1295,42 @@ impl Battle {
             }
             self.combatants.push(new);
         }
+        Ok(())
     }
 
-    /// Add damage to selected.
+    /// Add damage to the group selection, or the selected combatant.
     fn damage(&mut self, dam: i32) -> Result<(), CombatError> {
-        if let Some(f) = self.sel {
-            match self.combatants[f] {
-                BattleRow::Done(ref mut c) => Ok(c.recv_hit(dam)),
-                BattleRow::Building(_) => Err(CombatError::NotBuilt),
+        let targets = self.built_targets()?;
+        for f in targets {
+            let (who, status) = match self.combatants[f] {
+                BattleRow::Done(ref mut c) => (c.name.clone(), c.recv_hit(dam)),
+                BattleRow::Building(_) => return Err(CombatError::NotBuilt),
+            };
+            self.record(LogEntry::Damage { who: who.clone(), amount: dam });
+            if let Some(new_status) = status {
+                self.record(LogEntry::StatusChange { who, new_status });
             }
-        } else {
-            Ok(())
         }
+        Ok(())
     }
 
     /// Perform an attack from selected to the current target, consuming attacks.
     fn attack(&mut self, dam: i32) -> Result<(), CombatError> {
         let t = self.pos;
         if let Some(f) = self.sel {
-            if self.combatants[f].done().is_none() || self.combatants[f].done().is_none() {
+            if self.combatants[f].done().is_none() || self.combatants[t].done().is_none() {
                 return Err(CombatError::NotBuilt);
             }
+            // spectators cannot be targeted
+            if self.combatants[t].done().map(|c| !c.in_combat()).unwrap_or(false) {
+                return Err(CombatError::NotInCombat);
+            }
             // We have to borrow self.combatants 2 times, so we need separate scopes:
             // - once to check that `from` can act and update it mutably
             // - once to update `to` mutably
             {
-                // we know from the earlier if statement that `from` is a combatant
-                let mut from = self.combatants[f].done_mut().unwrap();
+                // we know from the earlier if statement that both rows are combatants
+                let from = self.combatants[f].done_mut().unwrap();
                 if from.in_combat() {
                     if from.can_attack() {
                         from.deal_hit(dam);
@@ -535,16 +1341,23 @@ impl Battle {
                     return Err(CombatError::NotInCombat);
                 }
             }
-            {
+            let status = {
                 // as with `from` above
-                let mut to = self.combatants[t].done_mut().unwrap();
-                to.recv_hit(dam);
+                let to = self.combatants[t].done_mut().unwrap();
+                to.recv_hit(dam)
+            };
+            let from = self.combatants[f].done().unwrap().name.clone();
+            let to = self.combatants[t].done().unwrap().name.clone();
+            self.record(LogEntry::Attack { from, to: to.clone(), damage: dam });
+            if let Some(new_status) = status {
+                self.record(LogEntry::StatusChange { who: to, new_status });
             }
         }
         Ok(())
     }
 
     /// Change the selected combatant's attacks.
+    #[allow(dead_code)]
     fn set_attacks(&mut self, atts: Meter<u32>) {
         if let Some(f) = self.sel {
             match self.combatants[f] {
@@ -554,60 +1367,237 @@ impl Battle {
         }
     }
 
-    /// Change the selected combatant's hp.
-    fn set_hp(&mut self, hp: Meter<i32>) {
-        if let Some(f) = self.sel {
-            match self.combatants[f] {
-                BattleRow::Done(ref mut c) => c.hp = hp,
+    /// Change the hp of the combatant under the cursor.
+    fn hp(&mut self, hp: Meter<i32>) {
+        if self.pos < self.combatants.len() {
+            match self.combatants[self.pos] {
                 BattleRow::Building(ref mut cb) => cb.hp = Some(hp),
+                BattleRow::Done(ref mut c) => {
+                    c.hp.set_max(hp.max());
+                    c.hp.set_curr(hp.curr());
+                },
             }
+            let pos = self.pos;
+            self.try_build(pos);
         }
     }
 
-    /// Heal the selected combatant.
+    /// Heal the group selection, or the selected combatant.
     fn heal(&mut self, dam: i32) -> Result<(), CombatError> {
+        let targets = self.built_targets()?;
+        for f in targets {
+            let (who, status) = match self.combatants[f] {
+                BattleRow::Done(ref mut c) => (c.name.clone(), c.heal(dam)),
+                BattleRow::Building(_) => return Err(CombatError::NotBuilt),
+            };
+            self.record(LogEntry::Heal { who: who.clone(), amount: dam });
+            if let Some(new_status) = status {
+                self.record(LogEntry::StatusChange { who, new_status });
+            }
+        }
+        Ok(())
+    }
+
+    /// Grant temporary hp to the selected combatant.
+    fn grant_temp_hp(&mut self, amount: i32) -> Result<(), CombatError> {
         if let Some(f) = self.sel {
-            match self.combatants[f] {
-                BattleRow::Done(ref mut c) => Ok(c.heal(dam)),
-                BattleRow::Building(_) => Err(CombatError::NotBuilt),
+            self.combatants[f].done_mut().ok_or(CombatError::NotBuilt)?.grant_temp_hp(amount)?;
+        }
+        Ok(())
+    }
+
+    /// Revive the combatant under the cursor with the given hp.
+    fn revive(&mut self, hp: i32) -> Result<(), CombatError> {
+        if self.pos < self.combatants.len() {
+            let who = {
+                let c = self.combatants[self.pos].done_mut().ok_or(CombatError::NotBuilt)?;
+                c.revive(hp)?;
+                c.name.clone()
+            };
+            self.record(LogEntry::StatusChange { who, new_status: Status::Healthy });
+        }
+        Ok(())
+    }
+
+    /// Spend a spell slot of the combatant under the cursor.
+    fn cast(&mut self, lvl: usize) -> Result<(), CombatError> {
+        if self.pos < self.combatants.len() {
+            self.combatants[self.pos].done_mut().ok_or(CombatError::NotBuilt)?.cast(lvl)?;
+        }
+        Ok(())
+    }
+
+    /// Add an entry to the log for the current round.
+    fn record(&mut self, entry: LogEntry) {
+        match self.log.last_mut() {
+            Some(ref mut l) if l.round == self.round => {
+                l.entries.push(entry);
+                return;
+            },
+            _ => (),
+        }
+        self.log.push(RoundLog { round: self.round, entries: vec![entry] });
+    }
+
+    /// Return the number of entries in the log.
+    fn log_len(&self) -> usize {
+        self.log.iter().map(|l| l.entries.len()).sum()
+    }
+
+    /// Drop the most recent log entries until `len` remain.
+    fn truncate_log(&mut self, len: usize) {
+        let mut excess = self.log_len().saturating_sub(len);
+        while excess > 0 {
+            let last = match self.log.last_mut() {
+                Some(l) => l,
+                None => break,
+            };
+            let keep = last.entries.len().saturating_sub(excess);
+            excess -= last.entries.len() - keep;
+            last.entries.truncate(keep);
+            if last.entries.is_empty() {
+                self.log.pop();
             }
-        } else {
-            Ok(())
         }
     }
 
+    /// Return the log as text, with the most recent round first.
+    fn log_text(&self) -> String {
+        self.log.iter().rev()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Roll a saving throw for the combatant under the cursor.
+    fn save(&mut self, cat: SaveCategory, roll: i32) -> Result<(), CombatError> {
+        if self.pos < self.combatants.len() {
+            let c = self.combatants[self.pos].done().ok_or(CombatError::NotBuilt)?;
+            self.notice = Some(format!("{} rolls {} vs {} on {} save: {}", c.name, roll,
+                c.save_target(cat), cat, if c.saves(cat, roll) { "SAVED" } else { "FAILED" }));
+        }
+        Ok(())
+    }
+
+    /// Check whether the selected combatant's roll hits the combatant under the cursor.
+    fn resolve_hit(&mut self, roll: u32) -> Result<(), CombatError> {
+        if let Some(f) = self.sel {
+            if f < self.combatants.len() && self.pos < self.combatants.len() {
+                let from = self.combatants[f].done().ok_or(CombatError::NotBuilt)?;
+                let to = self.combatants[self.pos].done().ok_or(CombatError::NotBuilt)?;
+                let ac = to.effective_ac();
+                self.notice = Some(format!("{} rolls {} vs AC {} of {}: {}", from.name, roll,
+                    ac, to.name, if from.hits(roll, ac) { "HIT" } else { "MISS" }));
+            }
+        }
+        Ok(())
+    }
+
     fn down(&mut self) {
         if self.pos + 1 < self.combatants.len() {
             self.pos += 1;
         }
+        let visible = self.visible_rows();
+        if self.pos >= self.scroll + visible {
+            self.scroll = self.pos + 1 - visible;
+        }
     }
 
     fn up(&mut self) {
         if self.pos > 0 {
             self.pos -= 1;
         }
+        if self.pos < self.scroll {
+            self.scroll = self.pos;
+        }
+    }
+
+    /// Return how many combatants fit in the table,
+    /// keeping a line free for the scroll indicator.
+    fn visible_rows(&self) -> usize {
+        let table_mode = self.display_mode == DisplayMode::Table;
+        // margins, prompt, table borders, header and indicator
+        let mut used = 2 + 3 + 2 + 2 + 1;
+        if self.show_log && table_mode {
+            used += 8;
+        }
+        let detail = self.sel
+            .and_then(|i| self.combatants.get(i))
+            .and_then(|row| row.done());
+        if detail.is_some() && table_mode {
+            used += 10;
+        }
+        (self.size.height as usize).saturating_sub(used).max(1)
+    }
+
+    /// Return the index of the first combatant to show,
+    /// moving the viewport if needed to keep the cursor on screen.
+    fn first_visible(&self) -> usize {
+        let visible = self.visible_rows();
+        if self.pos < self.scroll {
+            self.pos
+        } else if self.pos >= self.scroll + visible {
+            self.pos + 1 - visible
+        } else {
+            self.scroll
+        }
     }
 
     /// Return xp earned by the selected combatant.
     fn get_xp(&mut self) -> Option<i32> {
         self.sel.and_then(|f| {
-            let ref comb = match self.combatants[f] {
-                BattleRow::Done(ref c) => Some(c),
-                BattleRow::Building(_) => None,
-            }?;
-            let n = self.combatants.len() as i32;
-            let team_bonus = self.combatants.iter()
-                .filter_map(|comb| match comb {
-                    BattleRow::Done(c) => Some(c),
-                    BattleRow::Building(_) => None,
-                })
-                .filter(|x| x.team == comb.team)
-                .fold(0, |acc, ref x| acc + (x.team_xp() / n));
+            let comb = self.combatants[f].done()?;
+            let team = self.iter_done()
+                .filter(|x| x.team == comb.team && !x.spectator)
+                .collect::<Vec<_>>();
+            // split the team's earnings between its members
+            let n = team.len().max(1) as i32;
+            let team_bonus = team.iter()
+                .fold(0, |acc, x| acc + (x.team_xp() / n));
             Some(comb.xp(team_bonus))
         })
     }
 }
 
+/// Return the colour for an hp meter based on the fraction remaining.
+fn hp_color(hp: &Meter<i32>) -> tui::style::Color {
+    use tui::style::Color;
+    if hp.curr() * 2 > hp.max() {
+        Color::Green
+    } else if hp.curr() * 4 >= hp.max() {
+        Color::Yellow
+    } else {
+        Color::Red
+    }
+}
+
+/// Recolours one column of a bordered table drawn in the same area,
+/// since tui tables only support a single style per row.
+struct ColumnColors<'a> {
+    x: u16,
+    width: u16,
+    colors: &'a [tui::style::Color],
+}
+
+impl<'a> tui::widgets::Widget for ColumnColors<'a> {
+    fn draw(&mut self, area: &tui::layout::Rect, buf: &mut tui::buffer::Buffer) {
+        // skip the border and the header with its blank line
+        let left = area.left() + 1 + self.x;
+        let top = area.top() + 3;
+        let right = (left + self.width).min(area.right().saturating_sub(1));
+        let bottom = area.bottom().saturating_sub(1);
+        for (i, &color) in self.colors.iter().enumerate() {
+            let y = top + i as u16;
+            if y >= bottom {
+                break;
+            }
+            for x in left..right {
+                buf.get_mut(x, y).set_fg(color);
+            }
+        }
+    }
+}
+
 fn draw(t: &mut Terminal<RawBackend>, b: &Battle) -> Result<(), Error> {
     use tui::widgets::{
         Widget, Table, Block, Row, Borders, Paragraph
@@ -616,13 +1606,28 @@ fn draw(t: &mut Terminal<RawBackend>, b: &Battle) -> Result<(), Error> {
     use tui::layout::{Group, Size, Direction};
 
     let row_style = Style::default().fg(Color::White);
-    let mut rows = vec![];
-    for comb in &b.combatants {
+    let spectator_style = Style::default().fg(Color::DarkGray);
+    // teams cycle through these colours, starting from team 1
+    let team_styles = [Color::Cyan, Color::Red, Color::Green, Color::Magenta, Color::Blue]
+        .iter().map(|&c| Style::default().fg(c)).collect::<Vec<_>>();
+    let mut row_datas = vec![];
+    let mut styles = vec![];
+    let mut hp_colors = vec![];
+    let start = b.first_visible();
+    let visible = b.visible_rows();
+    for (i, comb) in b.combatants.iter().enumerate().skip(start).take(visible) {
         let row_data = vec![
             match comb {
                 BattleRow::Done(c) => c.name.clone(),
                 BattleRow::Building(cb) => cb.name.clone(),
             },
+            match comb {
+                BattleRow::Done(c) => c.class.abbrev(),
+                BattleRow::Building(cb) => match cb.class {
+                    Some(ref t) => t.abbrev(),
+                    None => String::from(""),
+                },
+            },
             match comb {
                 BattleRow::Done(c) => c.team.to_string(),
                 BattleRow::Building(cb) => match cb.team {
@@ -651,6 +1656,13 @@ fn draw(t: &mut Terminal<RawBackend>, b: &Battle) -> Result<(), Error> {
                     None => String::from(""),
                 },
             },
+            match comb {
+                BattleRow::Done(c) => c.ac.to_string(),
+                BattleRow::Building(cb) => match cb.ac {
+                    Some(t) => t.to_string(),
+                    None => String::from(""),
+                },
+            },
             match comb {
                 BattleRow::Done(c) => c.thac0.to_string(),
                 BattleRow::Building(_) => String::from(""),
@@ -660,41 +1672,230 @@ fn draw(t: &mut Terminal<RawBackend>, b: &Battle) -> Result<(), Error> {
                 BattleRow::Building(_) => String::from(""),
             },
         ];
-        rows.push(Row::StyledData(row_data.into_iter(), &row_style));
+        let style = match comb.done() {
+            Some(c) if c.spectator => &spectator_style,
+            Some(c) if c.team > 0 => &team_styles[(c.team as usize - 1) % team_styles.len()],
+            _ => &row_style,
+        };
+        hp_colors.push(match comb.done() {
+            Some(c) if c.status != Status::Dead => hp_color(&c.hp),
+            _ => Color::Gray,
+        });
+        row_datas.push(row_data);
+        styles.push(if b.multi_sel.contains(&i) {
+            style.bg(Color::DarkGray)
+        } else {
+            *style
+        });
+    }
+    let hidden = b.combatants.len().saturating_sub(start + visible);
+    if hidden > 0 {
+        row_datas.push(vec![format!("\u{2193} {} more", hidden)]);
+        styles.push(Style::default().fg(Color::Yellow));
+    }
+    let rows = row_datas.into_iter().zip(styles.iter())
+        .map(|(data, style)| Row::StyledData(data.into_iter(), style));
+
+    // the log and detail panels sit between the table and the prompt
+    let table_mode = b.display_mode == DisplayMode::Table && b.mode != Mode::Help;
+    let show_log = b.show_log && table_mode;
+    let detail = b.sel
+        .and_then(|i| b.combatants.get(i))
+        .and_then(|row| row.done())
+        .filter(|_| table_mode)
+        .map(|c| c.describe());
+    let mut sizes = vec![Size::Min(1)];
+    if show_log {
+        sizes.push(Size::Fixed(8));
     }
+    if detail.is_some() {
+        sizes.push(Size::Fixed(10));
+    }
+    sizes.push(Size::Fixed(3));
+    let log = b.log_text();
+    let widths = [16, 6, 1, 1, 9, 5, 3, 2, 2];
+    let hp_col = 4;
 
     Group::default()
         .direction(Direction::Vertical)
         .margin(1)
-        .sizes(&[Size::Min(1), Size::Fixed(3)])
+        .sizes(&sizes)
         .render(t, &b.size, |t, chunks| {
-            Table::new(
-                ["Name", "T", "I", "HP", "Att", "AC", "TH", ""].into_iter(),
-                rows.into_iter()
-                )
-                .block(Block::default().title(&format!("Round: {}", b.round)).borders(Borders::ALL))
-                .header_style(Style::default().fg(Color::Yellow))
-                .widths(&[16, 1, 1, 9, 5, 2, 2, 1])
-                .style(Style::default().fg(Color::White))
-                .column_spacing(1)
-                .render(t, &chunks[0]);
+            if b.mode == Mode::Help {
+                Paragraph::default()
+                    .style(Style::default().fg(Color::White))
+                    .block(Block::default().borders(Borders::ALL))
+                    .raw(true)
+                    .text(HELP)
+                    .render(t, &chunks[0]);
+            } else if b.display_mode == DisplayMode::DMScreen {
+                draw_dm_screen(t, b, &chunks[0]);
+            } else {
+                Table::new(
+                    ["Name", "Cl", "T", "I", "HP", "Att", "AC", "TH", ""].iter(),
+                    rows.into_iter()
+                    )
+                    .block(Block::default().title(&match b.spectator_count() {
+                        0 => format!("Round: {}", b.round),
+                        n => format!("Round: {} ({} watching)", b.round, n),
+                    }).borders(Borders::ALL))
+                    .header_style(Style::default().fg(Color::Yellow))
+                    .widths(&widths)
+                    .style(Style::default().fg(Color::White))
+                    .column_spacing(1)
+                    .render(t, &chunks[0]);
+                ColumnColors {
+                    x: widths[..hp_col].iter().map(|w| w + 1).sum(),
+                    width: widths[hp_col],
+                    colors: &hp_colors,
+                }.render(t, &chunks[0]);
+            }
+            if show_log {
+                Paragraph::default()
+                    .style(Style::default().fg(Color::White))
+                    .block(Block::default().title("Log").borders(Borders::ALL))
+                    .raw(true)
+                    .text(&log)
+                    .render(t, &chunks[1]);
+            }
+            if let Some(ref detail) = detail {
+                Paragraph::default()
+                    .style(Style::default().fg(Color::White))
+                    .block(Block::default().title("Detail").borders(Borders::ALL))
+                    .raw(true)
+                    .scroll(b.detail_scroll.min(detail.lines().count().saturating_sub(1) as u16))
+                    .text(detail)
+                    .render(t, &chunks[chunks.len() - 2]);
+            }
             Paragraph::default()
-                .style(Style::default().fg(Color::Yellow))
-                .block(Block::default().title("Prompt"))
+                .style(Style::default().fg(match b.error {
+                    Some(_) => Color::Red,
+                    None => Color::Yellow,
+                }))
+                .block(Block::default().title(&match b.autosave {
+                    Some(ref a) => format!("Prompt (autosave every {})", a.interval),
+                    None => String::from("Prompt (autosave off)"),
+                }))
                 .text(match b.mode {
+                    Mode::Insert(MsgType::OpenFileName) if !b.recent_saves.is_empty() => {
+                        let recent = b.recent_saves.iter().enumerate()
+                            .map(|(i, p)| format!("{}: {}", i + 1, p))
+                            .collect::<Vec<_>>()
+                            .join("  ");
+                        format!("> {} (or number: {}): {}", MsgType::OpenFileName, recent, b.input)
+                    },
                     Mode::Insert(p) => format!("> {}: {}", p, b.input),
                     //Mode::Char => format!("> {:?}: {}", b.requests[0], b.input),
                     //Mode::Command => format!("{:?}", p),
-                    _ => "".into(),
+                    _ => b.error.clone()
+                        .or_else(|| b.notice.clone())
+                        .or_else(|| b.cmd_prefix.map(|n| n.to_string()))
+                        .unwrap_or_default(),
                 }.as_str())
-                .render(t, &chunks[1]);
+                .render(t, &chunks[chunks.len() - 1]);
         });
 
     t.draw()?;
     Ok(())
 }
 
+/// Draw the DM screen: the selected combatant's details over the encounter notes
+/// on the left and the combat log on the right, each scrolled independently.
+fn draw_dm_screen(t: &mut Terminal<RawBackend>, b: &Battle, area: &tui::layout::Rect) {
+    use tui::widgets::{Widget, Block, Borders, Paragraph};
+    use tui::style::{Style, Color};
+    use tui::layout::{Group, Size, Direction};
+
+    let detail = b.sel.or(Some(b.pos))
+        .and_then(|i| b.combatants.get(i))
+        .map(|row| match row {
+            BattleRow::Done(c) => c.describe(),
+            BattleRow::Building(cb) => format!("{} (not yet built)", cb.name),
+        })
+        .unwrap_or_default();
+    let log = b.log_text();
+    // never scroll past the last line of a panel
+    let clamp = |scroll: u16, text: &str| scroll.min(text.lines().count().saturating_sub(1) as u16);
+
+    Group::default()
+        .direction(Direction::Horizontal)
+        .sizes(&[Size::Percent(50), Size::Percent(50)])
+        .render(t, area, |t, chunks| {
+            Group::default()
+                .direction(Direction::Vertical)
+                .sizes(&[Size::Min(1), Size::Fixed(5)])
+                .render(t, &chunks[0], |t, left| {
+                    Paragraph::default()
+                        .style(Style::default().fg(Color::White))
+                        .block(Block::default().title("Detail").borders(Borders::ALL))
+                        .raw(true)
+                        .wrap(true)
+                        .scroll(clamp(b.detail_scroll, &detail))
+                        .text(&detail)
+                        .render(t, &left[0]);
+                    Paragraph::default()
+                        .style(Style::default().fg(Color::White))
+                        .block(Block::default().title("Encounter notes").borders(Borders::ALL))
+                        .raw(true)
+                        .wrap(true)
+                        .text(&b.notes)
+                        .render(t, &left[1]);
+                });
+            Paragraph::default()
+                .style(Style::default().fg(Color::White))
+                .block(Block::default().title(&format!("Log (round {})", b.round)).borders(Borders::ALL))
+                .raw(true)
+                .scroll(clamp(b.log_scroll, &log))
+                .text(&log)
+                .render(t, &chunks[1]);
+        });
+}
+
+/// Options given on the command line.
+#[derive(Debug, Default)]
+struct Args {
+    /// Save file to open on startup
+    open: Option<String>,
+    autosave_prefix: Option<String>,
+    no_autosave: bool,
+}
+
+impl Args {
+    /// Parse the program's arguments, excluding the program name.
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, Error> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--autosave-prefix" => {
+                    let prefix = args.next()
+                        .ok_or_else(|| format_err!("--autosave-prefix requires a prefix"))?;
+                    parsed.autosave_prefix = Some(prefix);
+                },
+                "--no-autosave" => parsed.no_autosave = true,
+                a if a.starts_with("--") => bail!("Unknown option {}", a),
+                _ => parsed.open = Some(arg),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
 fn main() -> Result<(), Error> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    let backend = RawBackend::new()?;
+    let mut term = Terminal::new(backend)?;
+    let mut b = Battle::with_size(term.size()?);
+    if args.no_autosave {
+        b.autosave = None;
+    } else if let Some(prefix) = args.autosave_prefix {
+        b.autosave = Some(AutosaveSettings { prefix, ..AutosaveSettings::default() });
+    }
+    if let Some(ref open) = args.open {
+        if let Err(e) = b.load_combat(open) {
+            b.error = Some(format!("Could not open {}: {}", open, e));
+        }
+    }
+
     // Start input thread
     let (tx, rx) = mpsc::channel();
     let input_tx = tx.clone();
@@ -710,11 +1911,8 @@ fn main() -> Result<(), Error> {
         }
     });
 
-    let backend = RawBackend::new()?;
-    let mut term = Terminal::new(backend)?;
     term.clear()?;
     term.hide_cursor()?;
-    let mut b = Battle::new();
 
     loop {
         let size = term.size()?;
@@ -728,12 +1926,10 @@ fn main() -> Result<(), Error> {
         let evt = rx.recv().unwrap();
         match evt {
             Event::Input(Char('q')) => break,
-            Event::Input(F(1)) => {
-                // display help
-            }
             _ => {
-                // TODO: display possible errors
-                b.update(evt).ok();
+                if let Err(e) = b.update(evt) {
+                    b.error = Some(e.to_string());
+                }
             },
         }
     }
@@ -743,3 +1939,300 @@ fn main() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use combatants::Class;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use termion::event::Key::{Char, Ctrl};
+
+    /// Return a battle that does not write autosaves.
+    fn battle() -> Battle {
+        let mut b = Battle::new();
+        b.autosave = None;
+        b
+    }
+
+    /// Return a level 1 fighter with the given team, initiative and dexterity.
+    fn fighter(name: &str, team: u32, init: u32, dex: u32) -> CombatantBuilder {
+        CombatantBuilder::new(name)
+            .class(Classes::Single { name: Class::Fighter, lvl: 1 })
+            .abilities(format!("12/10/10/{}/10/10", dex).parse::<Abilities>().unwrap())
+            .hd(1u32)
+            .hp(Meter::new(8, 8))
+            .attacks(Meter::new(1u32, 1))
+            .ac(5)
+            .team(team)
+            .init(init)
+    }
+
+    #[test]
+    fn four_way_initiative_tie_is_broken() {
+        let mut b = battle();
+        for &(name, dex) in &[("A", 9), ("B", 15), ("C", 6), ("D", 12)] {
+            b.add_combatant(fighter(name, 1, 10, dex)).unwrap();
+        }
+        let groups = b.initiative_tie_groups();
+        assert_eq!(groups.values().map(|g| g.len()).collect::<Vec<_>>(), vec![4]);
+        assert!(b.has_initiative_ties());
+
+        b.break_ties_by_dex_roll(&mut StdRng::seed_from_u64(1208));
+        b.sort();
+        // each combatant rolls a d3 on top of the shared initiative
+        assert!(b.iter_done().all(|c| c.init >= 11 && c.init <= 13));
+        // four combatants on three values must tie again, so dexterity decides those
+        let order = b.iter_done().map(|c| (c.get_init(), c.dex())).collect::<Vec<_>>();
+        assert!(order.windows(2).all(|w| w[0] >= w[1]));
+    }
+
+    #[test]
+    fn load_rejects_too_many_combatants() {
+        let path = std::env::temp_dir().join("flesh-wounds-too-many.json");
+        let mut full = battle();
+        for name in &["A", "B", "C"] {
+            full.add_combatant(fighter(name, 1, 10, 10)).unwrap();
+        }
+        full.save_combat(&path).unwrap();
+
+        let mut small = Battle::with_max_combatants(2);
+        let err = small.load_combat(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        match err.downcast::<BattleError>() {
+            Ok(BattleError::TooManyCombatants(2)) => (),
+            r => panic!("expected TooManyCombatants, got {:?}", r),
+        }
+        assert!(small.combatants.is_empty());
+    }
+
+    #[test]
+    fn team_bonus_is_split_between_teammates_only() {
+        let mut b = battle();
+        for &(name, team) in &[("A", 1), ("B", 1), ("C", 2), ("D", 2), ("E", 2)] {
+            b.add_combatant(fighter(name, team, 10, 10)).unwrap();
+        }
+        // A hits C for 4 and B hits D for 2
+        for &(from, to, dam) in &[(0, 2, 4), (1, 3, 2)] {
+            b.sel = Some(from);
+            b.pos = to;
+            b.attack(dam).unwrap();
+        }
+        // team 1 earns 20 per point dealt, split two ways: (80 + 40) / 2
+        b.sel = Some(0);
+        assert_eq!(b.get_xp(), Some(4 * 10 + 60));
+        b.sel = Some(1);
+        assert_eq!(b.get_xp(), Some(2 * 10 + 60));
+        // team 2 dealt nothing, so C only earns for the damage taken
+        b.sel = Some(2);
+        assert_eq!(b.get_xp(), Some(4 * 20));
+    }
+
+    #[test]
+    fn sort_breaks_ties_by_dex_then_previous_order() {
+        let mut b = battle();
+        for &(name, dex) in &[("P", 10), ("Q", 14), ("R", 10), ("S", 14)] {
+            b.combatants.push(BattleRow::Done(fighter(name, 1, 10, dex).build().unwrap()));
+        }
+        let names = |b: &Battle| b.iter_done().map(|c| c.name.clone()).collect::<Vec<_>>();
+        b.sort();
+        assert_eq!(names(&b), vec!["Q", "S", "P", "R"]);
+        // sorting again keeps the same order
+        b.sort();
+        assert_eq!(names(&b), vec!["Q", "S", "P", "R"]);
+    }
+
+    #[test]
+    fn count_prefix_is_capped() {
+        let mut b = battle();
+        for c in "12345".chars() {
+            b.update(Event::Input(event::Key::Char(c))).unwrap();
+        }
+        assert_eq!(b.cmd_prefix, Some(12));
+        b.cmd_prefix = None;
+        for c in "99".chars() {
+            b.update(Event::Input(event::Key::Char(c))).unwrap();
+        }
+        assert_eq!(b.cmd_prefix, Some(MAX_CMD_PREFIX));
+    }
+
+    #[test]
+    fn attacking_a_row_being_built_is_an_error() {
+        let mut b = battle();
+        b.add_combatant(fighter("A", 1, 10, 10)).unwrap();
+        b.combatants.push(BattleRow::Building(CombatantBuilder::new("B")));
+        b.sel = Some(0);
+        b.pos = 1;
+        assert_eq!(b.attack(3), Err(CombatError::NotBuilt));
+        // nothing was spent on the failed attack
+        assert_eq!(b.combatants[0].done().unwrap().attacks.curr(), 1);
+        b.sel = Some(1);
+        b.pos = 0;
+        assert_eq!(b.attack(3), Err(CombatError::NotBuilt));
+    }
+
+    #[test]
+    fn attacking_self_spends_an_attack_and_takes_the_damage() {
+        let mut b = battle();
+        b.add_combatant(fighter("A", 1, 10, 10)).unwrap();
+        b.sel = Some(0);
+        b.pos = 0;
+        b.attack(3).unwrap();
+        let a = b.combatants[0].done().unwrap();
+        assert_eq!(a.hp.curr(), 5);
+        assert_eq!(a.attacks.curr(), 0);
+        assert_eq!(b.attack(3), Err(CombatError::NotEnoughAttacks));
+    }
+
+    #[test]
+    fn round_of_combat_through_updates() {
+        let mut b = Battle::with_size(tui::layout::Rect { x: 0, y: 0, width: 80, height: 24 });
+        b.autosave = None;
+        for &(name, init) in &[("Slow", 3), ("Fast", 9)] {
+            b.combatants.push(BattleRow::Done(fighter(name, 1, init, 10).build().unwrap()));
+        }
+        let names = |b: &Battle| b.iter_done().map(|c| c.name.clone()).collect::<Vec<_>>();
+        let send = |b: &mut Battle, keys: &[event::Key]| {
+            for &k in keys {
+                b.update(Event::Input(k)).unwrap();
+            }
+        };
+        // sort, select the second in order, then hit them for 3
+        send(&mut b, &[Ctrl('t')]);
+        assert_eq!(names(&b), vec!["Fast", "Slow"]);
+        send(&mut b, &[Char('j'), Char('\n')]);
+        assert_eq!(b.sel, Some(1));
+        send(&mut b, &[Char('d'), Char('3'), Char('\n')]);
+        let slow = b.combatants[1].done().unwrap();
+        assert_eq!(slow.hp, Meter::new(5, 8));
+        assert_eq!(slow.status, Status::Stunned(2));
+        // the next round counts the stun down
+        send(&mut b, &[Char('x')]);
+        assert_eq!(b.round, 2);
+        assert_eq!(names(&b), vec!["Fast", "Slow"]);
+        let (fast, slow) = (b.combatants[0].done().unwrap(), b.combatants[1].done().unwrap());
+        assert_eq!((fast.hp, fast.status), (Meter::new(8, 8), Status::Healthy));
+        assert_eq!((slow.hp, slow.status), (Meter::new(5, 8), Status::Stunned(1)));
+        assert_eq!(b.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn keystrokes_take_no_undo_snapshot() {
+        let mut b = battle();
+        b.add_combatant(fighter("A", 1, 10, 10)).unwrap();
+        for &k in &[Char('j'), Char('k'), Char('\n'), Char('d'), Char('1'), Char('2')] {
+            b.update(Event::Input(k)).unwrap();
+        }
+        assert!(b.history.is_empty());
+        b.update(Event::Input(Char('\n'))).unwrap();
+        assert_eq!(b.history.len(), 1);
+    }
+
+    #[test]
+    fn undo_drops_the_log_entries_it_reverts() {
+        let mut b = battle();
+        b.add_combatant(fighter("A", 1, 10, 10)).unwrap();
+        b.sel = Some(0);
+        let mut lens = vec![];
+        for &dam in &['3', '4'] {
+            for &k in &[Char('d'), Char(dam), Char('\n')] {
+                b.update(Event::Input(k)).unwrap();
+            }
+            lens.push(b.log_len());
+        }
+        assert!(lens[0] > 0 && lens[1] > lens[0]);
+        b.update(Event::Input(Ctrl('z'))).unwrap();
+        assert_eq!(b.log_len(), lens[0]);
+        assert_eq!(b.combatants[0].done().unwrap().hp.curr(), 5);
+        b.update(Event::Input(Ctrl('z'))).unwrap();
+        assert_eq!(b.log_len(), 0);
+        assert!(b.log.is_empty());
+        assert_eq!(b.combatants[0].done().unwrap().hp.curr(), 8);
+    }
+
+    #[test]
+    fn group_damage_with_an_unbuilt_target_changes_nobody() {
+        let mut b = battle();
+        b.add_combatant(fighter("A", 1, 10, 10)).unwrap();
+        b.combatants.push(BattleRow::Building(CombatantBuilder::new("B")));
+        b.multi_sel = vec![0, 1];
+        assert_eq!(b.damage(3), Err(CombatError::NotBuilt));
+        assert_eq!(b.heal(3), Err(CombatError::NotBuilt));
+        assert_eq!(b.combatants[0].done().unwrap().hp, Meter::new(8, 8));
+        assert_eq!(b.log_len(), 0);
+    }
+
+    /// Return autosave settings writing into a fresh temporary directory.
+    fn temp_autosave(name: &str, max_saves: u32, interval: u32) -> AutosaveSettings {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let prefix = format!("{}/", dir.display());
+        AutosaveSettings { prefix, max_saves, interval }
+    }
+
+    #[test]
+    fn autosave_counts_only_changes() {
+        let mut b = battle();
+        b.add_combatant(fighter("A", 1, 10, 10)).unwrap();
+        b.autosave = Some(temp_autosave("flesh-wounds-autosave-changes", 5, 2));
+        b.sel = Some(0);
+        // moving, typing and cancelling a prompt change nothing
+        for &k in &[Char('j'), Char('k'), Char('d'), Char('5'), Ctrl('c'), Char('j')] {
+            b.update(Event::Input(k)).unwrap();
+        }
+        assert_eq!(b.action_count, 0);
+        for &k in &[Char('d'), Char('2'), Char('\n'), Char('x')] {
+            b.update(Event::Input(k)).unwrap();
+        }
+        assert_eq!(b.action_count, 2);
+        let saves = b.recent_autosaves();
+        assert_eq!(saves.len(), 1);
+        assert!(Path::new(&saves[0]).exists());
+        std::fs::remove_dir_all(std::env::temp_dir().join("flesh-wounds-autosave-changes")).unwrap();
+    }
+
+    #[test]
+    fn saves_within_a_second_get_distinct_names() {
+        let a = temp_autosave("flesh-wounds-autosave-names", 5, 1);
+        let first = a.get_save_path();
+        a.record_save(first.clone()).unwrap();
+        let second = a.get_save_path();
+        assert_ne!(first, second);
+        std::fs::remove_dir_all(std::env::temp_dir().join("flesh-wounds-autosave-names")).unwrap();
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_saves() {
+        let mut b = battle();
+        b.add_combatant(fighter("A", 1, 10, 10)).unwrap();
+        b.autosave = Some(temp_autosave("flesh-wounds-autosave-prune", 2, 1));
+        let mut written = vec![];
+        for _ in 0..3 {
+            b.autosave().unwrap();
+            written.push(b.recent_autosaves()[0].clone());
+        }
+        let a = b.autosave.take().unwrap();
+        let dir = std::env::temp_dir().join("flesh-wounds-autosave-prune");
+        let mut files = std::fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().path().display().to_string())
+            .collect::<Vec<_>>();
+        files.sort();
+        let saves = a.read_manifest();
+        // the first save made is the one deleted
+        assert_eq!(saves, written[1..].to_vec());
+        assert!(!Path::new(&written[0]).exists());
+        let mut expected = saves.clone();
+        expected.push(a.manifest_path());
+        expected.sort();
+        assert_eq!(files, expected);
+
+        // a file listed twice by an older manifest is only deleted with its latest entry
+        let dup = vec![saves[0].clone(), saves[1].clone(), saves[0].clone()];
+        serde_json::to_writer(File::create(a.manifest_path()).unwrap(), &dup).unwrap();
+        a.record_save(saves[1].clone()).unwrap();
+        assert_eq!(a.read_manifest(), vec![saves[0].clone(), saves[1].clone()]);
+        assert!(Path::new(&saves[0]).exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,10 +6,29 @@ use std::str::FromStr;
 use std::num::ParseIntError;
 
 /// Struct for tracking the total of .0 out of .1
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+/// Meters are ordered by their current value, then by their maximum.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub struct Meter<T: Copy + Clone>(T, T);
 
 impl<T: Copy + Clone> Meter<T> {
+    /// Create a new meter at `curr` out of `max`.
+    ///
+    /// Panics if `curr` exceeds `max`.
+    pub fn new(curr: T, max: T) -> Self where T: PartialOrd + fmt::Debug {
+        assert!(curr <= max, "meter value {:?} exceeds maximum {:?}", curr, max);
+        Meter(curr, max)
+    }
+
+    /// Create a new meter at `curr` out of `max`,
+    /// returning an error instead of panicking if `curr` exceeds `max`.
+    pub fn try_new(curr: T, max: T) -> Result<Self, ParseMeterError> where T: PartialOrd {
+        if curr > max {
+            Err(ParseMeterError::ExceedsMax)
+        } else {
+            Ok(Meter(curr, max))
+        }
+    }
+
     pub fn curr(&self) -> T {
         self.0
     }
@@ -19,14 +38,39 @@ impl<T: Copy + Clone> Meter<T> {
     }
 }
 
-impl<T : Copy + Clone + FromStr<Err = ParseIntError>> FromStr for Meter<T> {
-    type Err = ParseIntError;
+impl<T: Copy + Clone + Default> Default for Meter<T> {
+    /// Create an empty meter.
+    fn default() -> Self {
+        Meter(T::default(), T::default())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum ParseMeterError {
+    #[fail(display = "Invalid integer value")]
+    Int(#[cause] ParseIntError),
+    #[fail(display = "Current value must not exceed the maximum")]
+    ExceedsMax,
+}
+
+impl From<ParseIntError> for ParseMeterError {
+    fn from(e: ParseIntError) -> Self {
+        ParseMeterError::Int(e)
+    }
+}
+
+impl<T : Copy + Clone + PartialOrd + FromStr<Err = ParseIntError>> FromStr for Meter<T> {
+    type Err = ParseMeterError;
     /// Parse a string depicting a fraction as a Meter.
+    /// A single value is used as both the current and maximum values.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let terms : Vec<&str> = s.split("/").collect();
         let curr_t = terms[0].parse::<T>()?;
-        let max_t = terms[1].parse::<T>()?;
-        Ok(Meter(curr_t, max_t))
+        let max_t = match terms.get(1) {
+            Some(t) => t.parse::<T>()?,
+            None => curr_t,
+        };
+        Meter::try_new(curr_t, max_t)
     }
 }
 
@@ -50,6 +94,40 @@ impl<T: Copy + Clone + Ord + Sub<Output = T>> Sub<T> for Meter<T> {
     }
 }
 
+impl<T: Copy + Clone + Add<Output = T>> Meter<T> {
+    /// Raise both the current and maximum values.
+    pub fn raise(self, rhs: T) -> Self {
+        Meter(self.0 + rhs, self.1 + rhs)
+    }
+}
+
+impl<T: Copy + Clone + Ord> Meter<T> {
+    /// Set the current value, capped at the maximum.
+    pub fn set_curr(&mut self, v: T) {
+        self.0 = v.min(self.1);
+    }
+
+    /// Set the maximum value, lowering the current value if it now exceeds it.
+    pub fn set_max(&mut self, v: T) {
+        self.1 = v;
+        self.0 = self.0.min(v);
+    }
+}
+
+impl<T: Copy + Clone + Default + Ord + Sub<Output = T>> Meter<T> {
+    /// Subtract from the current value, flooring at zero instead of underflowing.
+    pub fn saturating_sub(self, rhs: T) -> Self {
+        if rhs > self.0 {
+            Meter(T::default(), self.1)
+        } else {
+            Meter(self.0 - rhs, self.1)
+        }
+    }
+}
+
+/// Subtraction is not floored: hp meters go below zero as combatants fall
+/// unconscious and die. Use `saturating_sub` for meters that must stay non-negative,
+/// such as unsigned attack counts.
 impl<T: Copy + Clone + Ord + SubAssign> SubAssign<T> for Meter<T> {
     fn sub_assign(&mut self, rhs: T) {
         self.0 -= rhs;
@@ -63,7 +141,7 @@ impl<T: Copy + Clone + fmt::Display> fmt::Display for Meter<T> {
 }
 
 /// Struct for tracking the total amount of .0, which increases each turn by .1
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 pub struct Incrementer(f64, f64);
 
 #[allow(dead_code)]
@@ -72,6 +150,11 @@ impl Incrementer {
         Incrementer(0.0, incr)
     }
 
+    /// Create an incrementer that has already accumulated `start`.
+    pub fn new_with_start(start: f64, incr: f64) -> Self {
+        Incrementer(start, incr)
+    }
+
     pub fn curr(&self) -> f64 {
         self.0
     }
@@ -80,6 +163,21 @@ impl Incrementer {
         self.0 += self.1;
     }
 
+    /// Clear the accumulated total.
+    pub fn reset(&mut self) {
+        self.0 = 0.0;
+    }
+
+    /// Set the accumulated total, flooring at zero.
+    pub fn set(&mut self, v: f64) {
+        self.0 = v.max(0.0);
+    }
+
+    /// Change the amount added each turn.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.1 = rate;
+    }
+
     pub fn decr(&mut self, x: f64) {
         // prevent from decrementing into negatives
         self.0 -= if self.0 < x { self.0 } else { x };
@@ -91,3 +189,75 @@ impl fmt::Display for Incrementer {
         write!(f, "{:.2}/{:.2}", self.0, self.1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn new_keeps_values() {
+        let m = Meter::new(3, 10);
+        assert_eq!((m.curr(), m.max()), (3, 10));
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_panics_above_max() {
+        Meter::new(11, 10);
+    }
+
+    #[test]
+    fn try_new_rejects_above_max() {
+        assert_eq!(Meter::try_new(10, 10), Ok(Meter::new(10, 10)));
+        assert_eq!(Meter::try_new(11, 10), Err(ParseMeterError::ExceedsMax));
+    }
+
+    #[test]
+    fn saturating_sub_floors_at_zero() {
+        assert_eq!(Meter::new(2u32, 10u32).saturating_sub(5u32), Meter::new(0u32, 10u32));
+        assert_eq!(Meter::new(7u32, 10u32).saturating_sub(5u32), Meter::new(2u32, 10u32));
+    }
+
+    #[test]
+    fn sub_assign_goes_below_zero() {
+        let mut hp = Meter::new(3, 10);
+        hp -= 5;
+        assert_eq!(hp.curr(), -2);
+    }
+
+    #[test]
+    fn parse_fraction() {
+        assert_eq!("4/9".parse::<Meter<i32>>(), Ok(Meter::new(4, 9)));
+        assert_eq!("-2/9".parse::<Meter<i32>>(), Ok(Meter::new(-2, 9)));
+    }
+
+    #[test]
+    fn parse_single_value_fills_meter() {
+        assert_eq!("7".parse::<Meter<u32>>(), Ok(Meter::new(7, 7)));
+    }
+
+    #[test]
+    fn parse_rejects_curr_above_max() {
+        assert_eq!("12/10".parse::<Meter<i32>>(), Err(ParseMeterError::ExceedsMax));
+    }
+
+    #[test]
+    fn parse_rejects_non_numbers() {
+        match "a/10".parse::<Meter<i32>>() {
+            Err(ParseMeterError::Int(_)) => (),
+            r => panic!("expected an integer error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn ordered_by_curr_then_max() {
+        assert!(Meter::new(3, 10) < Meter::new(4, 10));
+        assert!(Meter::new(4, 10) <= Meter::new(4, 10));
+        assert_eq!(Meter::new(4, 10).partial_cmp(&Meter::new(4, 10)), Some(Ordering::Equal));
+        // a tie on the current value falls back to the maximum
+        assert!(Meter::new(5, 10) > Meter::new(5, 8));
+        // the current value takes precedence over the maximum
+        assert!(Meter::new(6, 8) > Meter::new(5, 10));
+    }
+}